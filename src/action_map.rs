@@ -0,0 +1,299 @@
+use super::controls::{Action, AxisDirection, Binding, Controls, Modifier, ACTIONS};
+use super::input_state::InputState;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::EventPump;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// Rebinding only picks up axes pushed past about half of their range
+static AXIS_REBIND_THRESHOLD: i16 = 16_000;
+
+static BUTTONS: [Button; 15] = [
+  Button::A,
+  Button::B,
+  Button::X,
+  Button::Y,
+  Button::Back,
+  Button::Guide,
+  Button::Start,
+  Button::LeftStick,
+  Button::RightStick,
+  Button::LeftShoulder,
+  Button::RightShoulder,
+  Button::DPadUp,
+  Button::DPadDown,
+  Button::DPadLeft,
+  Button::DPadRight,
+];
+
+static AXES: [Axis; 6] =
+  [Axis::LeftX, Axis::LeftY, Axis::RightX, Axis::RightY, Axis::TriggerLeft, Axis::TriggerRight];
+
+// Everything bindings are matched against during one tick
+#[derive(Default)]
+pub struct InputSnapshot {
+  pub keys: HashSet<Keycode>,
+  pub mouse_buttons: HashSet<MouseButton>,
+  pub gamepad_buttons: HashSet<Button>,
+  pub gamepad_axes: HashMap<Axis, i16>,
+}
+
+impl InputSnapshot {
+  pub fn capture(event_pump: &EventPump, game_controllers: &[GameController]) -> Self {
+    let mut snapshot = Self {
+      keys: event_pump
+        .keyboard_state()
+        .pressed_scancodes()
+        .filter_map(Keycode::from_scancode)
+        .collect(),
+      mouse_buttons: event_pump.mouse_state().pressed_mouse_buttons().collect(),
+      ..Self::default()
+    };
+    for game_controller in game_controllers {
+      snapshot
+        .gamepad_buttons
+        .extend(BUTTONS.iter().filter(|button| game_controller.button(**button)));
+      for axis in &AXES {
+        // With several gamepads the one pushed furthest wins
+        let value = game_controller.axis(*axis);
+        let strongest = snapshot.gamepad_axes.entry(*axis).or_insert(0);
+        if (value as i32).abs() > (*strongest as i32).abs() {
+          *strongest = value;
+        }
+      }
+    }
+    snapshot
+  }
+  // Presses that were released again before the tick ends still count for this tick
+  pub fn record(&mut self, event: &Event) {
+    match event {
+      Event::KeyDown { keycode: Some(key), .. } => {
+        self.keys.insert(*key);
+      }
+      Event::MouseButtonDown { mouse_btn, .. } => {
+        self.mouse_buttons.insert(*mouse_btn);
+      }
+      Event::ControllerButtonDown { button, .. } => {
+        self.gamepad_buttons.insert(*button);
+      }
+      _ => {}
+    }
+  }
+  fn modifiers(&self) -> HashSet<Modifier> {
+    self.keys.iter().filter_map(|key| Modifier::of_key(*key)).collect()
+  }
+  // How far a binding is pressed from 0 to 1, axes start past the deadzone.
+  // Plain keys ignore Shift so they can be combined with it, chords need exactly their modifiers.
+  pub fn binding_value(&self, binding: &Binding, deadzone: i16) -> f32 {
+    let is_pressed = |is_pressed: bool| if is_pressed { 1.0 } else { 0.0 };
+    match binding {
+      Binding::Key { key, modifiers } => {
+        if !self.keys.contains(key) {
+          return 0.0;
+        }
+        let held_modifiers = self
+          .modifiers()
+          .into_iter()
+          .filter(|modifier| Some(*modifier) != Modifier::of_key(*key))
+          .collect::<HashSet<_>>();
+        is_pressed(if modifiers.is_empty() {
+          held_modifiers.iter().all(|modifier| *modifier == Modifier::Shift)
+        } else {
+          held_modifiers == modifiers.iter().cloned().collect()
+        })
+      }
+      Binding::MouseButton(button) => is_pressed(self.mouse_buttons.contains(button)),
+      Binding::GamepadButton(button) => is_pressed(self.gamepad_buttons.contains(button)),
+      Binding::GamepadAxis { axis, direction } => {
+        let value = self.gamepad_axes.get(axis).cloned().unwrap_or(0) as f32;
+        let value = if *direction == AxisDirection::Negative { -value } else { value };
+        ((value - deadzone as f32) / (i16::MAX as f32 - deadzone as f32)).clamp(0.0, 1.0)
+      }
+    }
+  }
+  // The device producer of input states, the right stick aims. Shooting with the mouse is part of
+  // mouse aiming, without it clicks don't fire along the keyboard aim.
+  pub fn input_state(&self, controls: &Controls) -> InputState {
+    let deadzone = controls.gamepad.stick_deadzone;
+    let actions = ACTIONS
+      .iter()
+      .map(|action| {
+        let value = controls
+          .bindings(*action)
+          .iter()
+          .filter(|binding| {
+            controls.mouse_aim
+              || *action != Action::Shoot
+              || !matches!(binding, Binding::MouseButton(_))
+          })
+          .map(|binding| self.binding_value(binding, deadzone))
+          .fold(0.0, f32::max);
+        (*action, value)
+      })
+      .filter(|(_, value)| *value > 0.0)
+      .collect();
+    let aim_direction = self.stick_angle(Axis::RightX, Axis::RightY, controls.gamepad.aim_deadzone);
+    InputState { actions, aim_direction }
+  }
+  // The angle a stick points at, with y pointing down like in the level. The deadzone is radial,
+  // so diagonals aren't cut off.
+  fn stick_angle(&self, x_axis: Axis, y_axis: Axis, deadzone: i16) -> Option<f32> {
+    let x = self.gamepad_axes.get(&x_axis).cloned().unwrap_or(0) as f32;
+    let y = self.gamepad_axes.get(&y_axis).cloned().unwrap_or(0) as f32;
+    if x.hypot(y) > deadzone as f32 {
+      Some(y.atan2(x))
+    } else {
+      None
+    }
+  }
+}
+
+// The binding a rebinding prompt takes from an event. Modifier keys only bind on release,
+// so holding one first turns the next key into a chord.
+pub fn binding_from_event(event: &Event) -> Option<Binding> {
+  match event {
+    Event::KeyDown { keycode: Some(key), keymod, .. } if Modifier::of_key(*key).is_none() => {
+      let modifiers = [
+        (Mod::LCTRLMOD | Mod::RCTRLMOD, Modifier::Ctrl),
+        (Mod::LSHIFTMOD | Mod::RSHIFTMOD, Modifier::Shift),
+        (Mod::LALTMOD | Mod::RALTMOD, Modifier::Alt),
+      ]
+      .iter()
+      .filter(|(flags, _)| keymod.intersects(*flags))
+      .map(|(_, modifier)| *modifier)
+      .collect();
+      Some(Binding::Key { key: *key, modifiers })
+    }
+    Event::KeyUp { keycode: Some(key), .. } if Modifier::of_key(*key).is_some() => {
+      Some(Binding::Key { key: *key, modifiers: vec![] })
+    }
+    Event::MouseButtonDown { mouse_btn, .. } => Some(Binding::MouseButton(*mouse_btn)),
+    Event::ControllerButtonDown { button, .. } => Some(Binding::GamepadButton(*button)),
+    Event::ControllerAxisMotion { axis, value, .. }
+      if (*value as i32).abs() > AXIS_REBIND_THRESHOLD as i32 =>
+    {
+      let direction = if *value > 0 { AxisDirection::Positive } else { AxisDirection::Negative };
+      Some(Binding::GamepadAxis { axis: *axis, direction })
+    }
+    _ => None,
+  }
+}
+
+// The input state of this tick, compared against the previous tick for edges
+pub struct ActionMap {
+  state: InputState,
+  pressed: HashSet<Action>,
+  previously_pressed: HashSet<Action>,
+  // Actions whose press was already used up, they stay released until they are let go
+  consumed: HashSet<Action>,
+}
+
+impl ActionMap {
+  pub fn new() -> Self {
+    Self {
+      state: InputState::default(),
+      pressed: HashSet::new(),
+      previously_pressed: HashSet::new(),
+      consumed: HashSet::new(),
+    }
+  }
+  pub fn update(&mut self, state: InputState) {
+    let pressed: HashSet<Action> =
+      state.actions.iter().filter(|(_, value)| **value > 0.0).map(|(action, _)| *action).collect();
+    self.consumed.retain(|action| pressed.contains(action));
+    let pressed = pressed.difference(&self.consumed).cloned().collect();
+    self.previously_pressed = mem::replace(&mut self.pressed, pressed);
+    self.state = state;
+  }
+  pub fn consume(&mut self, actions: &[Action]) {
+    for action in actions {
+      if self.pressed.remove(action) {
+        self.consumed.insert(*action);
+      }
+    }
+  }
+  // 1 for keys and buttons, analog bindings report how far they are pushed
+  pub fn value(&self, action: Action) -> f32 {
+    self.state.actions.get(&action).cloned().unwrap_or(0.0)
+  }
+  pub fn aim_direction(&self) -> Option<f32> {
+    self.state.aim_direction
+  }
+  pub fn is_pressed(&self, action: Action) -> bool {
+    self.pressed.contains(&action)
+  }
+  pub fn is_just_pressed(&self, action: Action) -> bool {
+    self.pressed.contains(&action) && !self.previously_pressed.contains(&action)
+  }
+  // Nothing in the game reacts to releases yet, but replays and scripts are checked with it
+  #[allow(dead_code)]
+  pub fn is_just_released(&self, action: Action) -> bool {
+    !self.pressed.contains(&action) && self.previously_pressed.contains(&action)
+  }
+}
+
+#[test]
+fn action_map_reports_edges_and_keeps_chords_apart() {
+  let controls = Controls::deserialize(std::fs::read_to_string("config/controls.json").unwrap());
+  let mut actions = ActionMap::new();
+
+  let mut snapshot = InputSnapshot::default();
+  snapshot.keys.insert(Keycode::S);
+  actions.update(snapshot.input_state(&controls));
+  assert!(actions.is_just_pressed(Action::Down));
+  assert!(!actions.is_pressed(Action::SaveLevel));
+
+  snapshot.keys.insert(Keycode::LCtrl);
+  actions.update(snapshot.input_state(&controls));
+  assert!(actions.is_just_released(Action::Down));
+  assert!(actions.is_just_pressed(Action::SaveLevel));
+
+  actions.update(snapshot.input_state(&controls));
+  assert!(actions.is_pressed(Action::SaveLevel));
+  assert!(!actions.is_just_pressed(Action::SaveLevel));
+
+  // A consumed press only counts again once the keys were let go
+  actions.consume(&[Action::SaveLevel]);
+  actions.update(snapshot.input_state(&controls));
+  assert!(!actions.is_pressed(Action::SaveLevel));
+  actions.update(InputSnapshot::default().input_state(&controls));
+  actions.update(snapshot.input_state(&controls));
+  assert!(actions.is_just_pressed(Action::SaveLevel));
+}
+
+#[test]
+fn mouse_shooting_needs_mouse_aim() {
+  let mut controls =
+    Controls::deserialize(std::fs::read_to_string("config/controls.json").unwrap());
+  let mut snapshot = InputSnapshot::default();
+  snapshot.mouse_buttons.insert(MouseButton::Left);
+
+  controls.mouse_aim = false;
+  assert!(!snapshot.input_state(&controls).actions.contains_key(&Action::Shoot));
+  controls.mouse_aim = true;
+  assert!(snapshot.input_state(&controls).actions.contains_key(&Action::Shoot));
+}
+
+#[test]
+fn gamepad_axes_respect_the_deadzone() {
+  let controls = Controls::deserialize(std::fs::read_to_string("config/controls.json").unwrap());
+  let mut actions = ActionMap::new();
+  let mut snapshot = InputSnapshot::default();
+
+  snapshot.gamepad_axes.insert(Axis::LeftX, -controls.gamepad.stick_deadzone / 2);
+  actions.update(snapshot.input_state(&controls));
+  assert!(!actions.is_pressed(Action::Left));
+
+  snapshot.gamepad_axes.insert(Axis::LeftX, i16::MIN);
+  actions.update(snapshot.input_state(&controls));
+  assert!(actions.is_just_pressed(Action::Left));
+  assert_eq!(actions.value(Action::Left), 1.0);
+  assert!(!actions.is_pressed(Action::Right));
+
+  snapshot.gamepad_axes.insert(Axis::RightY, i16::MAX);
+  actions.update(snapshot.input_state(&controls));
+  assert_eq!(actions.aim_direction(), Some(std::f32::consts::FRAC_PI_2));
+}
@@ -7,6 +7,18 @@ mod level;
 #[path = "editor_menu.rs"]
 mod editor_menu;
 
+#[path = "editor_history.rs"]
+mod editor_history;
+
+#[path = "editor_snapping.rs"]
+mod editor_snapping;
+
+#[path = "sprite_picker.rs"]
+mod sprite_picker;
+
+#[path = "entity_inspector.rs"]
+mod entity_inspector;
+
 #[path = "text.rs"]
 mod text;
 
@@ -16,39 +28,134 @@ mod ui;
 #[path = "controls.rs"]
 mod controls;
 
+#[path = "action_map.rs"]
+mod action_map;
+
+#[path = "gamepad.rs"]
+mod gamepad;
+
+#[path = "input_state.rs"]
+mod input_state;
+
+#[path = "hot_reload.rs"]
+mod hot_reload;
+
+#[path = "display.rs"]
+mod display;
+
+#[path = "debug_overlay.rs"]
+mod debug_overlay;
+
+#[path = "profiler.rs"]
+mod profiler;
+
+#[path = "capture.rs"]
+mod capture;
+
+#[path = "dialog.rs"]
+mod dialog;
+
+#[path = "localization.rs"]
+mod localization;
+
+#[path = "settings.rs"]
+mod settings;
+
+#[path = "pause_menu.rs"]
+mod pause_menu;
+
+#[path = "game_state.rs"]
+mod game_state;
+
+#[path = "level_select.rs"]
+mod level_select;
+
+#[path = "hud.rs"]
+mod hud;
+
+#[path = "settings_screen.rs"]
+mod settings_screen;
+
+#[cfg(test)]
+#[path = "visual_regression.rs"]
+mod visual_regression;
+
 #[path = "entity.rs"]
 mod entity;
 
+use action_map::{binding_from_event, ActionMap, InputSnapshot};
 use camera::Camera;
-use controls::Controls;
-use editor_menu::EditorMenu;
-use entity::Entity;
+use capture::{render_frames, save_screenshot};
+use controls::{Action, Controls};
+use debug_overlay::draw_debug_overlay;
+use dialog::{Dialog, DialogScript};
+use display::Display;
+use editor_menu::{EditorConfig, EditorMenu, LevelEntityVariant};
+use entity::{Entity, EventType};
+use game_state::GameState;
+use gamepad::Gamepads;
+use hot_reload::FileWatcher;
+use hud::{draw_hud, HudConfig, HudValues, Inventory};
+use input_state::{InputProducer, InputRecorder, LiveInput, ScriptedInput};
 use level::Level;
+use level_select::{format_time, BestTimes, LevelSelect};
+use localization::{check_string_tables, Localization};
+use pause_menu::{PauseMenu, PauseMenuEntry};
+use profiler::{FramePhase, Profiler};
 use sdl2::audio::{AudioCVT, AudioCallback, AudioSpecDesired, AudioSpecWAV};
-use sdl2::event::{Event, WindowEvent};
+use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, WindowContext};
+use settings::Settings;
+use settings_screen::{SettingsChange, SettingsScreen};
+use sprite_picker::SpriteAtlas;
 use std::cmp;
-use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use std::mem;
 use std::path::Path;
 use std::time::{Duration, SystemTime};
-use text::show_text_line;
-use ui::{draw_edit_menu, draw_pause_menu};
+use text::{
+  find_missing_glyphs, measure_text, measure_text_line, show_text_line, Font, FontDescriptor,
+  TextLayout,
+};
+use ui::{draw_edit_menu, draw_message_panel, draw_reticle, draw_text_panel, PANEL_PADDING};
 
 static BACKGROUND_COLOR: Color = Color { r: 42, g: 43, b: 37, a: 0xff };
+static LETTERBOX_COLOR: Color = Color { r: 0, g: 0, b: 0, a: 0xff };
 
 static MAX_FRAME_TIME_MILLIS: u64 = 16;
 
+static PROFILER_HISTORY_LENGTH: usize = 120;
+
 static INITIAL_WINDOW_WIDTH: u16 = 900;
 static INITIAL_WINDOW_HEIGHT: u16 = 600;
 
+static CONTROLS_PATH: &str = "config/controls.json";
+static UI_SPRITE_SHEET_PATH: &str = "assets/spritesheets/ui.bmp";
+static FILE_ERROR_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+
+fn set_fullscreen(canvas: &mut WindowCanvas, fullscreen: bool) {
+  let fullscreen_type = if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+  if let Err(error) = canvas.window_mut().set_fullscreen(fullscreen_type) {
+    println!("couldn't change fullscreen mode: {}", error);
+  }
+}
+
+// Black is transparent in every sprite sheet
+fn load_texture<'a>(
+  texture_creator: &'a TextureCreator<WindowContext>,
+  file_path: &str,
+) -> Result<Texture<'a>, String> {
+  let mut surface = Surface::load_bmp(Path::new(file_path))?;
+  surface.set_color_key(true, Color { r: 0, g: 0, b: 0, a: 0xff })?;
+  texture_creator.create_texture_from_surface(&surface).map_err(|error| error.to_string())
+}
+
 fn concatenate(a: &[Entity], b: &[Entity], c: &[Entity]) -> Vec<Entity> {
   [a, b, c].concat()
 }
@@ -57,6 +164,7 @@ struct Sound {
   data: Vec<u8>,
   pos: usize,
   is_loop: bool,
+  volume: f32,
 }
 
 impl AudioCallback for Sound {
@@ -66,7 +174,7 @@ impl AudioCallback for Sound {
     for dst in out.iter_mut() {
       let pre_scale = *self.data.get(self.pos).unwrap_or(&128);
       let scaled_signed_float = pre_scale as f32 - 128.0;
-      let scaled = (scaled_signed_float + 128.0) as u8;
+      let scaled = (scaled_signed_float * self.volume + 128.0) as u8;
       *dst = scaled;
       if self.is_loop {
         self.pos = (self.pos + 1) % self.data.len();
@@ -77,10 +185,71 @@ impl AudioCallback for Sound {
   }
 }
 
-pub fn run(level_name: &str, sprite_sheet_name: &str) {
+pub fn capture(level_name: &str, sprite_sheet_name: &str, frame_count: u32) {
+  let display = Display::deserialize(fs::read_to_string("config/display.json").unwrap());
+  render_frames(level_name, sprite_sheet_name, frame_count, display.logical_resolution, "captures");
+}
+
+pub fn check_glyphs(font_name: &str) -> bool {
+  let descriptor = match FontDescriptor::deserialize(
+    fs::read_to_string(format!("assets/fonts/{}.json", font_name)).unwrap(),
+  ) {
+    Ok(descriptor) => descriptor,
+    Err(error) => {
+      println!("assets/fonts/{}.json: {}", font_name, error);
+      return false;
+    }
+  };
+  let (missing_glyphs, unreadable_files) =
+    find_missing_glyphs(&descriptor, &["assets/levels", "assets/dialogs", "assets/strings"]);
+  for (file_path, error) in &unreadable_files {
+    println!("{}: couldn't be checked: {}", file_path, error);
+  }
+  for (file_path, text, characters) in &missing_glyphs {
+    println!("{}: {:?} has no glyph for {:?}", file_path, text, characters);
+  }
+  missing_glyphs.is_empty() && unreadable_files.is_empty()
+}
+
+pub fn check_strings() -> bool {
+  let (missing_keys, unused_keys) =
+    check_string_tables(&["src", "assets/dialogs", "assets/levels"]);
+  for (language, key) in &missing_keys {
+    println!("{}: missing string {:?}", language, key);
+  }
+  for key in &unused_keys {
+    println!("unused string {:?}", key);
+  }
+  missing_keys.is_empty() && unused_keys.is_empty()
+}
+
+// A replay drives the game until it runs out, recordings are saved when the game is closed
+pub fn run(
+  level_name: &str,
+  sprite_sheet_name: &str,
+  replay_path: Option<&str>,
+  is_recording: bool,
+) {
+  let mut input_producer: Box<dyn InputProducer> = match replay_path {
+    Some(replay_path) => match ScriptedInput::load(replay_path) {
+      Ok(replay) => Box::new(replay),
+      Err(error) => {
+        println!("couldn't load replay {}: {}", replay_path, error);
+        Box::new(LiveInput)
+      }
+    },
+    None => Box::new(LiveInput),
+  };
+  let mut input_recorder = if is_recording { Some(InputRecorder::new()) } else { None };
+
   let sdl_context = sdl2::init().unwrap();
   let video_subsystem = sdl_context.video().unwrap();
   let audio_subsystem = sdl_context.audio().unwrap();
+  let game_controller_subsystem = sdl_context.game_controller().unwrap();
+  let mouse_util = sdl_context.mouse();
+
+  // Controllers only send events while they are open
+  let mut gamepads = Gamepads::open(game_controller_subsystem);
 
   let mut jump_sound = audio_subsystem
     .open_playback(None, &AudioSpecDesired { freq: None, channels: None, samples: None }, |spec| {
@@ -92,7 +261,7 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
 
       let data = cvt.convert(wav.buffer().to_vec());
 
-      Sound { data, pos: 0, is_loop: false }
+      Sound { data, pos: 0, is_loop: false, volume: 1.0 }
     })
     .unwrap();
 
@@ -106,11 +275,11 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
 
       let data = cvt.convert(wav.buffer().to_vec());
 
-      Sound { data, pos: 0, is_loop: false }
+      Sound { data, pos: 0, is_loop: false, volume: 1.0 }
     })
     .unwrap();
 
-  let background_sound = audio_subsystem
+  let mut background_sound = audio_subsystem
     .open_playback(None, &AudioSpecDesired { freq: None, channels: None, samples: None }, |spec| {
       let wav = AudioSpecWAV::load_wav(Path::new("./assets/audio/background.wav")).unwrap();
 
@@ -120,7 +289,7 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
 
       let data = cvt.convert(wav.buffer().to_vec());
 
-      Sound { data, pos: 0, is_loop: true }
+      Sound { data, pos: 0, is_loop: true, volume: 1.0 }
     })
     .unwrap();
 
@@ -133,111 +302,134 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
     .build()
     .unwrap();
 
-  let mut canvas: WindowCanvas = window.into_canvas().build().unwrap();
+  sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+  let mut canvas: WindowCanvas = window.into_canvas().target_texture().build().unwrap();
   canvas.window_mut().set_minimum_size(350, 250).unwrap();
 
+  let mut display = Display::deserialize(fs::read_to_string("config/display.json").unwrap());
+  set_fullscreen(&mut canvas, display.fullscreen);
+
   let mut event_pump = sdl_context.event_pump().unwrap();
 
   video_subsystem.text_input().start();
 
-  let mut level =
-    Level::deserialize(fs::read_to_string(format!("assets/levels/{}.json", level_name)).unwrap());
+  let mut level = Level::load(level_name);
 
-  let controls = Controls::deserialize(fs::read_to_string("config/controls.json").unwrap());
+  let mut controls = Controls::deserialize(fs::read_to_string(CONTROLS_PATH).unwrap());
+  let mut settings = Settings::deserialize(fs::read_to_string("config/settings.json").unwrap());
+  jump_sound.lock().volume = settings.sound_volume as f32 / 100.0;
+  shoot_sound.lock().volume = settings.sound_volume as f32 / 100.0;
+  background_sound.lock().volume = settings.music_volume as f32 / 100.0;
+  let localization = Localization::load(&settings.language);
+  let hud_config = HudConfig::deserialize(fs::read_to_string("config/hud.json").unwrap())
+    .unwrap_or_else(|error| panic!("config/hud.json: {}", error));
+  let editor_config = EditorConfig::deserialize(fs::read_to_string("config/editor.json").unwrap());
 
   let texture_creator = canvas.texture_creator();
-  let (entity_texture, ui_texture, mut text_texture) = {
-    let mut texture_surface =
-      Surface::load_bmp(Path::new(&format!("assets/spritesheets/{}.bmp", sprite_sheet_name)))
-        .unwrap();
-    texture_surface.set_color_key(true, Color { r: 0, g: 0, b: 0, a: 0xff }).unwrap();
-
-    let mut ui_texture_surface =
-      Surface::load_bmp(Path::new("assets/spritesheets/ui.bmp")).unwrap();
-    ui_texture_surface.set_color_key(true, Color { r: 0, g: 0, b: 0, a: 0xff }).unwrap();
-
-    let mut text_texture_surface =
-      Surface::load_bmp(Path::new("assets/spritesheets/text.bmp")).unwrap();
-    text_texture_surface.set_color_key(true, Color { r: 128, g: 128, b: 128, a: 0xff }).unwrap();
-
-    (
-      texture_creator.create_texture_from_surface(&texture_surface).unwrap(),
-      texture_creator.create_texture_from_surface(&ui_texture_surface).unwrap(),
-      texture_creator.create_texture_from_surface(&text_texture_surface).unwrap(),
+  let sprite_sheet_path = format!("assets/spritesheets/{}.bmp", sprite_sheet_name);
+  let mut entity_texture = load_texture(&texture_creator, &sprite_sheet_path).unwrap();
+  let mut ui_texture = load_texture(&texture_creator, UI_SPRITE_SHEET_PATH).unwrap();
+
+  let mut file_watcher = FileWatcher::new();
+  file_watcher.watch(CONTROLS_PATH);
+  file_watcher.watch(&sprite_sheet_path);
+  let sprite_atlas_path = SpriteAtlas::path(sprite_sheet_name);
+  file_watcher.watch(&sprite_atlas_path);
+  file_watcher.watch(UI_SPRITE_SHEET_PATH);
+  file_watcher.watch(Level::path(level_name));
+  let mut file_error: Option<String> = None;
+  let mut font = Font::load("default", &texture_creator);
+  let mut frame_texture = texture_creator
+    .create_texture_target(
+      None,
+      display.logical_resolution.0 as u32,
+      display.logical_resolution.1 as u32,
     )
-  };
-
-  let mut paused = false;
+    .unwrap();
 
-  let mut camera = Camera::new((INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT));
-  let mut target_camera = Camera::new((INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT));
+  let mut game_state = GameState::Title;
+  let mut paused_state = GameState::Playing;
+  let mut level_name = level_name.to_string();
+  let mut best_times = BestTimes::load();
+  let mut level_select = LevelSelect::load(&best_times);
+  // Level time counts simulated ticks, so lag doesn't cost time
+  let mut level_time = Duration::from_secs(0);
+  let mut is_new_best_time = false;
+  let mut inventory = Inventory::default();
+  let mut pause_menu = PauseMenu::new();
+  let mut settings_screen = SettingsScreen::new();
+  let mut actions = ActionMap::new();
+  pause_menu.set_enabled(PauseMenuEntry::RestartFromCheckpoint, false);
+  let mut checkpoint: Option<((f32, f32), Inventory)> = None;
+
+  let mut camera = Camera::new(display.logical_resolution);
+  let mut target_camera = Camera::new(display.logical_resolution);
 
   let first_frame_time = SystemTime::now();
   let mut last_frame_time = first_frame_time.clone();
 
-  let mut edit_mode = false;
-  let mut editor_menu = EditorMenu::new();
+  let mut free_camera = false;
+  let mut show_debug_overlay = false;
+  let mut show_profiler = false;
+  let mut dialog: Option<Dialog> = None;
+  let mut last_triggered_dialog: Option<String> = None;
+  let mut profiler = Profiler::new(PROFILER_HISTORY_LENGTH);
+  let mut editor_menu = EditorMenu::new(&editor_config);
+  // The level as it was left in the editor. Play-testing runs on the level and this is put back
+  // when returning, so the editor history still addresses the right entities.
+  let mut edited_level: Option<Level> = None;
+  let mut was_editing = false;
+  let sprite_sheet_query = entity_texture.query();
+  editor_menu.sprite_picker.set_sheet_size((sprite_sheet_query.width, sprite_sheet_query.height));
+  editor_menu.sprite_picker.set_atlas(SpriteAtlas::load_optional(&sprite_atlas_path).unwrap());
 
   let mut mouse_click_position = None;
   let mut mouse_selection_rect: Option<Rect> = None;
 
   let mut last_shot = first_frame_time.clone();
   'running: loop {
-    canvas.set_draw_color(BACKGROUND_COLOR);
-    canvas.clear();
+    profiler.start_frame();
 
-    let ticks = first_frame_time.elapsed().unwrap().as_millis();
+    let mut take_screenshot = false;
 
-    let pressed_keys: HashSet<_> =
-      event_pump.keyboard_state().pressed_scancodes().filter_map(Keycode::from_scancode).collect();
+    let ticks = first_frame_time.elapsed().unwrap().as_millis();
 
     let (mouse_x, mouse_y) = {
       let mouse_state = event_pump.mouse_state();
-      (mouse_state.x(), mouse_state.y())
+      display.window_to_logical((mouse_state.x(), mouse_state.y()), canvas.window().size())
     };
 
-    let has_free_camera = edit_mode || paused;
+    let has_free_camera = game_state == GameState::Editing || free_camera;
+    // The reticle replaces the cursor while aiming with the mouse
+    let shows_reticle = controls.mouse_aim && game_state == GameState::Playing;
+    if mouse_util.is_cursor_showing() == shows_reticle {
+      mouse_util.show_cursor(!shows_reticle);
+    }
+    let mut chosen_pause_menu_entry = None;
+    let mut start_level: Option<bool> = None;
 
     let mut camera_commands: Vec<Box<dyn Fn(&mut Entity, &mut Camera)>> = vec![];
 
+    let mut input = InputSnapshot::capture(&event_pump, gamepads.controllers());
+    let mut new_binding = None;
+    let was_typing = editor_menu.is_typing();
+
     for event in event_pump.poll_iter() {
+      input.record(&event);
+      gamepads.handle_event(&event);
+      if game_state == GameState::Editing && editor_menu.is_typing() {
+        editor_menu.inspector_event(&mut level, &event);
+      }
+      if game_state == GameState::Settings
+        && settings_screen.is_waiting_for_binding()
+        && new_binding.is_none()
+      {
+        new_binding = binding_from_event(&event);
+      }
       match event {
         Event::Quit { .. } => {
           break 'running;
         }
-        Event::KeyDown { keycode: Some(key), .. } => {
-          if key == controls.pause_key {
-            if edit_mode {
-              mouse_click_position = None;
-            }
-            paused = !paused;
-          } else if key == controls.edit_mode_key {
-            edit_mode = !edit_mode;
-          } else if key == Keycode::S {
-            if pressed_keys.contains(&Keycode::LCtrl) || pressed_keys.contains(&Keycode::RCtrl) {
-              let file_path = {
-                let file_name =
-                  format!("{:x}.json", first_frame_time.elapsed().unwrap().as_nanos());
-                format!("assets/levels/{}", file_name)
-              };
-              let path = Path::new(&file_path);
-              let display = path.display();
-              match File::create(&path) {
-                Err(_) => panic!("couldn't create {}", display),
-                Ok(mut file) => {
-                  let level_serialized = level.serialize();
-                  println!("Saving now: {}", file_path);
-                  file
-                    .write_all(level_serialized.as_bytes())
-                    .expect(&format!("Save for file '{}' failed.", file_path))
-                }
-              };
-            }
-          }
-        }
-        Event::Window { win_event: WindowEvent::Resized(width, height), .. } => {
-          camera.dimensions = (width as u16, height as u16);
-        }
         Event::MouseWheel { y, .. } => {
           if has_free_camera {
             if y < 0 {
@@ -251,8 +443,30 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
             }
           }
         }
+        Event::MouseMotion { x, y, .. } => {
+          let position = display.window_to_logical((x, y), canvas.window().size());
+          if game_state == GameState::Paused {
+            pause_menu.hover(&font, &localization, camera.dimensions, position);
+          } else if game_state == GameState::Editing {
+            editor_menu.mouse_motion(&mut level, &camera, position);
+          }
+        }
+        Event::MouseButtonUp { .. } => {
+          if game_state == GameState::Editing {
+            editor_menu.mouse_up(&mut level, &camera);
+          }
+        }
         Event::MouseButtonDown { x, y, .. } => {
-          if has_free_camera {
+          let (x, y) = display.window_to_logical((x, y), canvas.window().size());
+          if game_state == GameState::Paused {
+            chosen_pause_menu_entry =
+              pause_menu.hover(&font, &localization, camera.dimensions, (x, y));
+          } else if game_state == GameState::Editing
+            && (editor_menu.inspector_click(&mut level, (x, y), camera.dimensions)
+              || editor_menu.sprite_picker_click(&mut level, (x, y), camera.dimensions))
+          {
+            // The editor panels cover the level, clicks on them stop there
+          } else if has_free_camera {
             let clicked_variant_button =
               EditorMenu::get_variant_button_rects().into_iter().find(|(_, rect, _)| {
                 x > rect.x()
@@ -260,13 +474,22 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
                   && y > rect.y()
                   && y < rect.y() + rect.height() as i32
               });
-            if mem::discriminant(&clicked_variant_button) == mem::discriminant(&None) {
+            if let Some((variant, _, _)) = clicked_variant_button {
+              editor_menu.variant(variant);
+            } else if editor_menu.variant == LevelEntityVariant::Selection {
+              if game_state == GameState::Editing {
+                let is_adding =
+                  input.keys.contains(&Keycode::LShift) || input.keys.contains(&Keycode::RShift);
+                editor_menu.mouse_down(&mut level, &camera, (x, y), is_adding);
+              }
+            } else {
               match mouse_click_position {
                 Some(_) => {
                   mouse_click_position = None;
 
                   editor_menu.create_entity(
                     &mut level,
+                    &camera,
                     Entity::from_canvas_coordinates(
                       (
                         mouse_selection_rect.unwrap().x() as f32,
@@ -286,8 +509,6 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
                   mouse_click_position = Some((x, y));
                 }
               }
-            } else if let Some((variant, _, _)) = clicked_variant_button {
-              editor_menu.variant(variant);
             }
           }
         }
@@ -295,37 +516,310 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
       }
     }
 
+    let mut live_input = input.input_state(&controls);
+    if controls.mouse_aim && game_state == GameState::Playing && live_input.aim_direction.is_none()
+    {
+      if let Some(main_character) = level.main_character.first() {
+        let target = Entity::canvas_to_world_position(
+          (mouse_x as f32, mouse_y as f32),
+          main_character.parallax,
+          &camera,
+          ((camera.dimensions.0 / 2) as u32, (camera.dimensions.1 / 2) as u32),
+        );
+        let center = (
+          main_character.position.0 + main_character.dimensions.0 as f32 / 2.0,
+          main_character.position.1 + main_character.dimensions.1 as f32 / 2.0,
+        );
+        live_input.aim_direction = Some((target.1 - center.1).atan2(target.0 - center.0));
+      }
+    }
+    let input_state = input_producer.next_state(live_input);
+    if let Some(recorder) = &mut input_recorder {
+      recorder.record(&input_state);
+    }
+    actions.update(input_state);
+    // Keys that ended the typing are still held, they only act once pressed again
+    let is_typing = was_typing || editor_menu.is_typing();
+
+    // Menus and dialogs take their actions first, global actions only see the rest
+    let mut is_handled = true;
+    match game_state {
+      GameState::Editing if is_typing => {}
+      GameState::Settings => {
+        match settings_screen.handle_input(
+          &actions,
+          new_binding,
+          &mut controls,
+          &mut settings,
+          &mut display,
+        ) {
+          SettingsChange::Close => game_state = GameState::Paused,
+          // Conflicting bindings are only shown, the file keeps the last usable controls
+          SettingsChange::Controls if !controls.has_conflicts() => controls.save(CONTROLS_PATH),
+          SettingsChange::Volume => {
+            jump_sound.lock().volume = settings.sound_volume as f32 / 100.0;
+            shoot_sound.lock().volume = settings.sound_volume as f32 / 100.0;
+            background_sound.lock().volume = settings.music_volume as f32 / 100.0;
+            settings.save("config/settings.json");
+          }
+          SettingsChange::Display => {
+            set_fullscreen(&mut canvas, display.fullscreen);
+            display.save("config/display.json");
+          }
+          SettingsChange::Controls | SettingsChange::None => {}
+        }
+      }
+      GameState::Title | GameState::LevelComplete if actions.is_just_pressed(Action::Confirm) => {
+        level_select = LevelSelect::load(&best_times);
+        level_select.select(&level_name);
+        game_state = GameState::LevelSelect;
+      }
+      GameState::LevelSelect if actions.is_just_pressed(Action::Confirm) => {
+        if let Some(selected_level) = level_select.selected_level() {
+          level_name = selected_level.file_name.clone();
+          start_level = Some(false);
+        }
+      }
+      GameState::LevelSelect if actions.is_just_pressed(Action::Up) => {
+        level_select.select_previous();
+      }
+      GameState::LevelSelect if actions.is_just_pressed(Action::Down) => {
+        level_select.select_next();
+      }
+      GameState::LevelSelect if actions.is_just_pressed(Action::Pause) => {
+        game_state = GameState::Title;
+      }
+      GameState::GameOver if actions.is_just_pressed(Action::Confirm) => {
+        start_level = Some(true);
+      }
+      GameState::GameOver if actions.is_just_pressed(Action::Pause) => {
+        level_select = LevelSelect::load(&best_times);
+        level_select.select(&level_name);
+        game_state = GameState::LevelSelect;
+      }
+      GameState::Paused if actions.is_just_pressed(Action::Confirm) => {
+        chosen_pause_menu_entry = Some(pause_menu.selected_entry());
+      }
+      GameState::Paused if actions.is_just_pressed(Action::Up) => {
+        pause_menu.select_previous();
+      }
+      GameState::Paused if actions.is_just_pressed(Action::Down) => {
+        pause_menu.select_next();
+      }
+      _ => is_handled = false,
+    }
+    if let Some(current_dialog) = dialog.as_mut().filter(|_| !is_handled) {
+      is_handled = true;
+      if actions.is_just_pressed(Action::Confirm) {
+        if !current_dialog.confirm() {
+          dialog = None;
+          // Gameplay actions on the same button wait until it is pressed again
+          actions.consume(&controls.sharing_bindings(Action::Confirm));
+        }
+      } else if actions.is_just_pressed(Action::Up) {
+        current_dialog.select_previous_choice();
+      } else if actions.is_just_pressed(Action::Down) {
+        current_dialog.select_next_choice();
+      } else {
+        is_handled = false;
+      }
+    }
+    if !is_handled {
+      if actions.is_just_pressed(Action::Pause) {
+        if game_state == GameState::Editing {
+          mouse_click_position = None;
+        }
+        game_state = game_state.toggle_pause(&mut paused_state);
+        pause_menu.reset();
+      }
+      if actions.is_just_pressed(Action::EditMode) {
+        game_state = game_state.toggle_editing();
+      }
+      if actions.is_just_pressed(Action::FreeCamera) {
+        free_camera = !free_camera;
+      }
+      if actions.is_just_pressed(Action::DebugOverlay) {
+        show_debug_overlay = !show_debug_overlay;
+      }
+      if actions.is_just_pressed(Action::Screenshot) {
+        take_screenshot = true;
+      }
+      if actions.is_just_pressed(Action::Profiler) {
+        show_profiler = !show_profiler;
+      }
+      if actions.is_just_pressed(Action::ProfilerDump) {
+        profiler
+          .dump_csv(&format!("profiles/{:x}.csv", first_frame_time.elapsed().unwrap().as_nanos()));
+      }
+      if game_state == GameState::Editing && actions.is_just_pressed(Action::Undo) {
+        editor_menu.undo(&mut level);
+      }
+      if game_state == GameState::Editing && actions.is_just_pressed(Action::Redo) {
+        editor_menu.redo(&mut level);
+      }
+      if game_state == GameState::Editing && actions.is_just_pressed(Action::Snapping) {
+        editor_menu.snapping.is_enabled = !editor_menu.snapping.is_enabled;
+      }
+      if game_state == GameState::Editing && actions.is_just_pressed(Action::SpritePicker) {
+        editor_menu.sprite_picker.is_open = !editor_menu.sprite_picker.is_open;
+      }
+      if game_state == GameState::Editing {
+        let nudges = [
+          (Action::NudgeLeft, (-1.0, 0.0)),
+          (Action::NudgeRight, (1.0, 0.0)),
+          (Action::NudgeUp, (0.0, -1.0)),
+          (Action::NudgeDown, (0.0, 1.0)),
+        ];
+        for (action, delta) in nudges.iter() {
+          if actions.is_just_pressed(*action) {
+            editor_menu.nudge(&mut level, *delta);
+          }
+        }
+      }
+      if actions.is_just_pressed(Action::SaveLevel) {
+        let file_path = {
+          let file_name = format!("{:x}.json", first_frame_time.elapsed().unwrap().as_nanos());
+          format!("assets/levels/{}", file_name)
+        };
+        let path = Path::new(&file_path);
+        let display = path.display();
+        match File::create(&path) {
+          Err(_) => panic!("couldn't create {}", display),
+          Ok(mut file) => {
+            let level_serialized = level.serialize();
+            println!("Saving now: {}", file_path);
+            file
+              .write_all(level_serialized.as_bytes())
+              .expect(&format!("Save for file '{}' failed.", file_path))
+          }
+        };
+      }
+    }
+
+    match chosen_pause_menu_entry {
+      Some(PauseMenuEntry::Resume) => game_state = paused_state,
+      Some(PauseMenuEntry::RestartLevel) => start_level = Some(false),
+      Some(PauseMenuEntry::RestartFromCheckpoint) => start_level = Some(true),
+      Some(PauseMenuEntry::ReturnToEditor) => game_state = GameState::Editing,
+      Some(PauseMenuEntry::Quit) => break 'running,
+      Some(PauseMenuEntry::Settings) => game_state = GameState::Settings,
+      None => {}
+    }
+
+    // Restarting from the checkpoint keeps the level time running
+    if let Some(from_checkpoint) = start_level {
+      editor_menu.replace_level(&mut level, Level::load(&level_name));
+      match checkpoint.clone().filter(|_| from_checkpoint) {
+        Some((position, checkpoint_inventory)) => {
+          level.main_character[0].position = position;
+          inventory = checkpoint_inventory;
+        }
+        None => {
+          checkpoint = None;
+          pause_menu.set_enabled(PauseMenuEntry::RestartFromCheckpoint, false);
+          level_time = Duration::from_secs(0);
+          inventory = Inventory::default();
+        }
+      }
+      dialog = None;
+      last_triggered_dialog = None;
+      game_state = GameState::Playing;
+      file_watcher.watch(Level::path(&level_name));
+      edited_level = None;
+    }
+
+    // A broken file keeps what was loaded before and shows the error until the next reload
+    for path in file_watcher.poll() {
+      let result = if path == Path::new(CONTROLS_PATH) {
+        Controls::try_load(CONTROLS_PATH).map(|reloaded_controls| controls = reloaded_controls)
+      } else if path == Path::new(&sprite_sheet_path) {
+        load_texture(&texture_creator, &sprite_sheet_path).map(|texture| {
+          let query = texture.query();
+          editor_menu.sprite_picker.set_sheet_size((query.width, query.height));
+          entity_texture = texture;
+        })
+      } else if path == Path::new(&sprite_atlas_path) {
+        SpriteAtlas::load_optional(&sprite_atlas_path)
+          .map(|atlas| editor_menu.sprite_picker.set_atlas(atlas))
+      } else if path == Path::new(UI_SPRITE_SHEET_PATH) {
+        load_texture(&texture_creator, UI_SPRITE_SHEET_PATH).map(|texture| ui_texture = texture)
+      } else if path == Path::new(&Level::path(&level_name))
+        && settings.hot_reload_level
+        && game_state.shows_level()
+      {
+        Level::try_load(&level_name).map(|mut reloaded_level| {
+          if let (Some(main_character), Some(reloaded_main_character)) =
+            (level.main_character.first(), reloaded_level.main_character.first_mut())
+          {
+            reloaded_main_character.position = main_character.position;
+            reloaded_main_character.velocity = main_character.velocity;
+          }
+          editor_menu.replace_level(&mut level, reloaded_level);
+          edited_level = None;
+        })
+      } else {
+        continue;
+      };
+      match result {
+        Ok(()) => {
+          println!("Reloaded {}", path.display());
+          file_error = None;
+        }
+        Err(error) => {
+          file_error = Some(localization.format(
+            "hot_reload.error",
+            &[("file", &path.display().to_string()), ("error", &error)],
+          ))
+        }
+      }
+    }
+
+    let is_editing = game_state == GameState::Editing;
+    if was_editing && !is_editing {
+      edited_level = Some(level.clone());
+    } else if !was_editing && is_editing {
+      if let Some(restored_level) = edited_level.take() {
+        level = restored_level;
+      }
+    }
+    was_editing = is_editing;
+
+    profiler.end_phase(FramePhase::Input);
+
     let entities = concatenate(&level.indestructible, &level.destructible, &level.enemies);
 
-    if has_free_camera {
-      if pressed_keys.contains(&controls.up_key) {
+    if has_free_camera && !is_typing {
+      if actions.is_pressed(Action::Up) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.position.1 -= 25.0 / current_camera.scale.1;
         }));
-      } else if pressed_keys.contains(&controls.down_key) {
+      } else if actions.is_pressed(Action::Down) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.position.1 += 25.0 / current_camera.scale.1;
         }));
       }
-      if pressed_keys.contains(&controls.left_key) {
+      if actions.is_pressed(Action::Left) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.position.0 -= 25.0 / current_camera.scale.0;
         }));
-      } else if pressed_keys.contains(&controls.right_key) {
+      } else if actions.is_pressed(Action::Right) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.position.0 += 25.0 / current_camera.scale.1;
         }));
       }
-      if pressed_keys.contains(&controls.zoom_in_key) {
+      if actions.is_pressed(Action::ZoomIn) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.zoom(1.03);
         }));
-      } else if pressed_keys.contains(&controls.zoom_out_key) {
+      } else if actions.is_pressed(Action::ZoomOut) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.zoom(0.97);
         }));
       }
-    } else {
+    } else if game_state == GameState::Playing
+      && dialog.is_none()
+      && !level.main_character.is_empty()
+    {
       camera_commands.push(Box::new(|_, current_camera| {
         current_camera.set_zoom(1.0);
       }));
@@ -333,11 +827,15 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
       let mut entity_commands: Vec<Box<dyn FnMut(&mut Entity)>> = vec![];
       let mut attack_commands: Vec<Box<dyn FnMut(&mut Entity, &mut Vec<Entity>)>> = vec![];
 
-      if pressed_keys.contains(&controls.shoot_key) {
+      let has_ammo = level.main_character[0].ammo != Some(0);
+      if actions.is_pressed(Action::Shoot) && has_ammo {
         if last_shot.elapsed().unwrap().as_millis() > 50 {
           last_shot = SystemTime::now();
           attack_commands.push(Box::new(|entity, level_container| {
             if let Some(aim_direction) = entity.aim_direction {
+              if let Some(ammo) = &mut entity.ammo {
+                *ammo -= 1;
+              }
               {
                 let mut lock = shoot_sound.lock();
                 (*lock).pos = 0;
@@ -356,7 +854,7 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
           }));
         }
       }
-      if pressed_keys.contains(&controls.jump_key) {
+      if actions.is_pressed(Action::Jump) {
         entity_commands.push(Box::new(|entity| {
           if entity.is_touching_ground(&entities) {
             entity.velocity.1 = -8.0;
@@ -379,20 +877,26 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
           entity.acceleration.1 = 1.0;
         }));
       }
-      if pressed_keys.contains(&controls.up_key) {
+      if let Some(aim_direction) = actions.aim_direction() {
+        entity_commands.push(Box::new(move |entity| {
+          if entity.aim_direction.is_some() {
+            entity.aim_direction = Some(aim_direction);
+          }
+        }));
+      } else if actions.is_pressed(Action::Up) {
         entity_commands.push(Box::new(|entity| {
           if let Some(aim_direction) = entity.aim_direction {
             entity.aim_direction = Some(aim_direction - std::f32::consts::PI / 30.0);
           }
         }));
-      } else if pressed_keys.contains(&controls.down_key) {
+      } else if actions.is_pressed(Action::Down) {
         entity_commands.push(Box::new(|entity| {
           if let Some(aim_direction) = entity.aim_direction {
             entity.aim_direction = Some(aim_direction + std::f32::consts::PI / 30.0);
           }
         }));
       }
-      let sprint_factor = if pressed_keys.contains(&controls.sprint_key) {
+      let sprint_factor = if actions.is_pressed(Action::Sprint) {
         camera_commands.push(Box::new(|_, current_camera| {
           current_camera.set_zoom(0.7);
         }));
@@ -400,12 +904,12 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
       } else {
         1.0
       };
-      if pressed_keys.contains(&controls.left_key) {
+      if actions.is_pressed(Action::Left) {
         camera_commands.push(Box::new(|entity, current_camera| {
           current_camera.position.0 = entity.position.0 - 400.0;
         }));
         entity_commands.push(Box::new(|entity| {
-          entity.velocity.0 = -5.0 * sprint_factor;
+          entity.velocity.0 = -5.0 * sprint_factor * actions.value(Action::Left);
           if ticks % (300.0 / sprint_factor) as u128 > (225.0 / sprint_factor) as u128 {
             entity.sprite_sheet_rect = Some((0, 0, 32, 32));
           } else if ticks % (300.0 / sprint_factor) as u128 > (150.0 / sprint_factor) as u128 {
@@ -416,12 +920,12 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
             entity.sprite_sheet_rect = Some((32, 0, 32, 32));
           }
         }));
-      } else if pressed_keys.contains(&controls.right_key) {
+      } else if actions.is_pressed(Action::Right) {
         camera_commands.push(Box::new(|entity, current_camera| {
           current_camera.position.0 = entity.position.0 + 400.0;
         }));
         entity_commands.push(Box::new(|entity| {
-          entity.velocity.0 = 5.0 * sprint_factor;
+          entity.velocity.0 = 5.0 * sprint_factor * actions.value(Action::Right);
           if ticks % (300.0 / sprint_factor) as u128 > (225.0 / sprint_factor) as u128 {
             entity.sprite_sheet_rect = Some((96, 0, 32, 32));
           } else if ticks % (300.0 / sprint_factor) as u128 > (150.0 / sprint_factor) as u128 {
@@ -433,10 +937,10 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
           }
         }));
       }
-      if !pressed_keys.contains(&controls.right_key)
-        && !pressed_keys.contains(&controls.down_key)
-        && !pressed_keys.contains(&controls.left_key)
-        && !pressed_keys.contains(&controls.up_key)
+      if !actions.is_pressed(Action::Right)
+        && !actions.is_pressed(Action::Down)
+        && !actions.is_pressed(Action::Left)
+        && !actions.is_pressed(Action::Up)
       {
         camera_commands.push(Box::new(|entity, current_camera| {
           current_camera.position = (
@@ -453,19 +957,76 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
         command(&mut level.main_character[0], &mut level.effects);
       }
     }
-    for command in camera_commands {
-      command(&mut level.main_character[0], &mut target_camera);
+    profiler.end_phase(FramePhase::EntityCommands);
+
+    if let Some(main_character) = level.main_character.first_mut() {
+      for command in camera_commands {
+        command(main_character, &mut target_camera);
+      }
     }
 
-    if !paused {
+    if game_state.is_simulating() && dialog.is_none() {
       (&mut level).next_state(&entities);
+      let is_damaged = level.main_character.iter().any(|main_character| {
+        main_character.triggered_events.iter().any(|event| matches!(event, EventType::Damage))
+      });
+      if is_damaged {
+        gamepads.rumble(controls.gamepad.rumble_strength, controls.gamepad.rumble_millis);
+      }
+      level_time += Duration::from_millis(MAX_FRAME_TIME_MILLIS);
+
+      // Dialogs and camera effects only start again after their trigger has been left
+      let mut triggered_dialog = None;
+      let mut camera_effects = vec![];
+      for triggered_event in level.take_triggered_events() {
+        match triggered_event {
+          EventType::CameraEffect(camera_effect) => camera_effects.push(camera_effect),
+          EventType::Dialog(name) => triggered_dialog = Some(name),
+          EventType::Checkpoint => {
+            checkpoint = level
+              .main_character
+              .first()
+              .map(|main_character| (main_character.position, inventory.clone()));
+            pause_menu.set_enabled(PauseMenuEntry::RestartFromCheckpoint, checkpoint.is_some());
+          }
+          EventType::Collect { item, score } => inventory.collect(&item, score),
+          EventType::LevelComplete => {
+            is_new_best_time = best_times.record(&level_name, level_time);
+            game_state = GameState::LevelComplete;
+          }
+          _ => {}
+        }
+      }
+      camera.apply_triggered_effects(camera_effects);
+      level.remove_collected_items();
+      if level.main_character.is_empty() {
+        game_state = GameState::GameOver;
+      }
+      if let Some(name) = &triggered_dialog {
+        if last_triggered_dialog.as_ref() != Some(name) {
+          match DialogScript::load(name, &localization) {
+            Ok(script) => dialog = Some(Dialog::new(script, &font.descriptor, camera.dimensions)),
+            Err(error) => {
+              file_error =
+                Some(localization.format("dialog.error", &[("name", name), ("error", &error)]))
+            }
+          }
+        }
+      }
+      last_triggered_dialog = triggered_dialog;
+    }
+    if let Some(current_dialog) = &mut dialog {
+      current_dialog.next_state();
     }
+    profiler.end_phase(FramePhase::LevelState);
+
+    camera.next_effects_state();
     if has_free_camera {
       camera.to_target(&target_camera, (0.3, 0.3));
-    } else {
+    } else if let Some(main_character) = level.main_character.first() {
       let main_character_camera_target = (
-        level.main_character[0].position.0 + level.main_character[0].dimensions.0 as f32 / 2.0,
-        level.main_character[0].position.1 + level.main_character[0].dimensions.1 as f32 / 2.0,
+        main_character.position.0 + main_character.dimensions.0 as f32 / 2.0,
+        main_character.position.1 + main_character.dimensions.1 as f32 / 2.0,
       );
       let distance_to_target_camera = {
         let x = main_character_camera_target.0 - camera.position.0;
@@ -474,62 +1035,204 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
         (x * x + y * y).sqrt()
       };
 
-      let velocity = (level.main_character[0].velocity.0 * level.main_character[0].velocity.0
-        + level.main_character[0].velocity.1 * level.main_character[0].velocity.1)
+      let velocity = (main_character.velocity.0 * main_character.velocity.0
+        + main_character.velocity.1 * main_character.velocity.1)
         .sqrt();
       let camera_smooth_speed_factor =
         0.06 * (distance_to_target_camera / 100.0) * (velocity / 75.0);
 
       camera.to_target(&target_camera, (camera_smooth_speed_factor, camera_smooth_speed_factor));
     }
-    level.draw(&mut camera, &mut canvas, &entity_texture);
+    profiler.end_phase(FramePhase::Camera);
+
+    canvas
+      .with_texture_canvas(&mut frame_texture, |canvas| {
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.clear();
+
+        if game_state.shows_level() {
+          level.draw(&mut camera, canvas, &entity_texture);
+          camera.draw_fade(canvas);
+          if show_debug_overlay {
+            draw_debug_overlay(canvas, &mut font, &level, &camera, &entities);
+          }
+          if game_state == GameState::Playing || game_state == GameState::Paused {
+            draw_hud(
+              canvas,
+              &mut font,
+              &ui_texture,
+              &localization,
+              &hud_config,
+              &HudValues {
+                health: level
+                  .main_character
+                  .first()
+                  .and_then(|main_character| main_character.health),
+                ammo: level.main_character.first().and_then(|main_character| main_character.ammo),
+                inventory: &inventory,
+                time: level_time,
+              },
+              camera.dimensions,
+            );
+          }
+          if shows_reticle {
+            draw_reticle(canvas, (mouse_x, mouse_y));
+          }
+          if let Some(current_dialog) = &dialog {
+            current_dialog.draw(canvas, &mut font, &entity_texture, camera.dimensions);
+          }
+        }
 
-    if paused {
-      draw_pause_menu(&mut canvas);
-    }
+        match game_state {
+          GameState::Title => {
+            let text = format!(
+              "{}\n{{#8c8c8c}}{}{{/}}",
+              localization.get("title.name"),
+              localization
+                .format("title.start", &[("key", &controls.binding_name(Action::Confirm))])
+            );
+            draw_message_panel(canvas, &mut font, &text, camera.dimensions);
+          }
+          GameState::LevelSelect => {
+            level_select.draw(canvas, &mut font, &localization, camera.dimensions);
+          }
+          GameState::Paused => {
+            pause_menu.draw(
+              canvas,
+              &mut font,
+              &localization,
+              &controls.binding_name(Action::Pause),
+              camera.dimensions,
+            );
+          }
+          GameState::LevelComplete => {
+            let best_time = if is_new_best_time {
+              localization.get("level_complete.new_best_time").to_string()
+            } else {
+              let time = best_times.get(&level_name).map(format_time).unwrap_or_default();
+              localization.format("level_complete.best_time", &[("time", &time)])
+            };
+            let text = format!(
+              "{}\n{}\n{}\n{{#8c8c8c}}{}{{/}}",
+              localization.get("level_complete.title"),
+              localization.format("level_complete.time", &[("time", &format_time(level_time))]),
+              best_time,
+              localization
+                .format("level_complete.hint", &[("key", &controls.binding_name(Action::Confirm))])
+            );
+            draw_message_panel(canvas, &mut font, &text, camera.dimensions);
+          }
+          GameState::GameOver => {
+            let text = format!(
+              "{}\n{{#8c8c8c}}{}{{/}}",
+              localization.get("game_over.title"),
+              localization.format(
+                "game_over.hint",
+                &[
+                  ("confirm", &controls.binding_name(Action::Confirm)),
+                  ("back", &controls.binding_name(Action::Pause)),
+                ]
+              )
+            );
+            draw_message_panel(canvas, &mut font, &text, camera.dimensions);
+          }
+          GameState::Settings => {
+            settings_screen.draw(canvas, &mut font, &localization, &controls, &settings, &display);
+          }
+          GameState::Playing | GameState::Editing => {}
+        }
 
-    if edit_mode {
-      let original_color = canvas.draw_color();
-      canvas.set_draw_color(Color { r: 255, g: 60, b: 60, a: 0xff });
-      // Crosshair to indicate center of frame
-      canvas
-        .draw_line(
-          (camera.dimensions.0 as i32 / 2, 0),
-          (camera.dimensions.0 as i32 / 2, camera.dimensions.1 as i32),
-        )
-        .unwrap();
-      canvas
-        .draw_line(
-          (0, camera.dimensions.1 as i32 / 2),
-          (camera.dimensions.0 as i32, camera.dimensions.1 as i32 / 2),
-        )
-        .unwrap();
-
-      draw_edit_menu(&mut canvas, &ui_texture, &editor_menu.variant);
-
-      match mouse_click_position {
-        Some((x, y)) => {
+        if game_state == GameState::Editing {
+          editor_menu.snapping.draw_grid(canvas, &camera);
+          let original_color = canvas.draw_color();
           canvas.set_draw_color(Color { r: 255, g: 60, b: 60, a: 0xff });
-          let (pos_x, width) = (cmp::min(x, mouse_x), (x - mouse_x).wrapping_abs() as u32);
-          let (pos_y, height) = (cmp::min(y, mouse_y), (y - mouse_y).wrapping_abs() as u32);
-          mouse_selection_rect = Some(Rect::new(pos_x, pos_y, width, height));
-          canvas.draw_rect(mouse_selection_rect.unwrap()).unwrap();
+          // Crosshair to indicate center of frame
+          canvas
+            .draw_line(
+              (camera.dimensions.0 as i32 / 2, 0),
+              (camera.dimensions.0 as i32 / 2, camera.dimensions.1 as i32),
+            )
+            .unwrap();
+          canvas
+            .draw_line(
+              (0, camera.dimensions.1 as i32 / 2),
+              (camera.dimensions.0 as i32, camera.dimensions.1 as i32 / 2),
+            )
+            .unwrap();
+
+          draw_edit_menu(canvas, &ui_texture, &editor_menu.variant);
+          editor_menu.draw_selection(canvas, &level, &camera);
+          editor_menu.draw_inspector(canvas, &mut font, &localization, &level, camera.dimensions);
+          editor_menu.draw_sprite_picker(
+            canvas,
+            &mut font,
+            &localization,
+            &entity_texture,
+            camera.dimensions,
+          );
+
+          match mouse_click_position {
+            Some((x, y)) => {
+              canvas.set_draw_color(Color { r: 255, g: 60, b: 60, a: 0xff });
+              let (pos_x, width) = (cmp::min(x, mouse_x), (x - mouse_x).wrapping_abs() as u32);
+              let (pos_y, height) = (cmp::min(y, mouse_y), (y - mouse_y).wrapping_abs() as u32);
+              mouse_selection_rect = Some(Rect::new(pos_x, pos_y, width, height));
+              canvas.draw_rect(mouse_selection_rect.unwrap()).unwrap();
+            }
+            None => {}
+          }
+          canvas.set_draw_color(original_color);
         }
-        None => {}
-      }
-      canvas.set_draw_color(original_color);
-    }
-    show_text_line(
-      &mut canvas,
-      &mut text_texture,
-      &format!("{}", 1_000_000 / last_frame_time.elapsed().unwrap().as_micros()),
-      (camera.dimensions.0 as i32 - 100, 10),
-      2,
-      1.1,
-      Color { r: 220, g: 140, b: 120, a: 0xff },
-    );
+        let frames_per_second =
+          format!("{}", 1_000_000 / last_frame_time.elapsed().unwrap().as_micros());
+        let (frames_per_second_width, _) = measure_text_line(&font, &frames_per_second, 2, 1.1);
+        show_text_line(
+          canvas,
+          &mut font,
+          &frames_per_second,
+          (camera.dimensions.0 as i32 - frames_per_second_width as i32 - 20, 10),
+          2,
+          1.1,
+          Color { r: 220, g: 140, b: 120, a: 0xff },
+        );
+        if show_profiler {
+          profiler.draw(canvas, &mut font, (10, camera.dimensions.1 as i32 - 150));
+        }
+        if let Some(error) = &file_error {
+          let layout = TextLayout::new(1);
+          let (width, height) = measure_text(&font, error, &layout);
+          draw_text_panel(
+            canvas,
+            &mut font,
+            error,
+            (
+              (camera.dimensions.0 as i32 - width as i32) / 2 - PANEL_PADDING,
+              camera.dimensions.1 as i32 - height as i32 - PANEL_PADDING * 4,
+            ),
+            &layout,
+            FILE_ERROR_COLOR,
+          );
+        }
+        if take_screenshot {
+          save_screenshot(
+            canvas,
+            (camera.dimensions.0 as u32, camera.dimensions.1 as u32),
+            &format!("screenshots/{:x}.bmp", first_frame_time.elapsed().unwrap().as_nanos()),
+          );
+        }
+      })
+      .unwrap();
+
+    profiler.end_phase(FramePhase::Draw);
 
+    canvas.set_draw_color(LETTERBOX_COLOR);
+    canvas.clear();
+    canvas
+      .copy(&frame_texture, None, Some(display.viewport(canvas.output_size().unwrap())))
+      .unwrap();
     canvas.present();
+    profiler.end_phase(FramePhase::Present);
+    profiler.end_frame();
 
     let millis_to_sleep: i64 =
       MAX_FRAME_TIME_MILLIS as i64 - last_frame_time.elapsed().unwrap().as_millis() as i64;
@@ -540,4 +1243,8 @@ pub fn run(level_name: &str, sprite_sheet_name: &str) {
     }
     last_frame_time = SystemTime::now();
   }
+
+  if let Some(recorder) = &input_recorder {
+    recorder.save(&format!("replays/{:x}.json", first_frame_time.elapsed().unwrap().as_nanos()));
+  }
 }
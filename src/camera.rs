@@ -1,18 +1,150 @@
 use super::entity::Entity;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, WindowCanvas};
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture};
 use serde::{Deserialize, Serialize};
 
+static MAX_SHAKE_OFFSET: f32 = 30.0;
+static TRAUMA_DECAY_PER_TICK: f32 = 0.02;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CameraEffect {
+  Shake(f32),
+  ZoomPunch(f32, u32),
+  PanTo(f32, f32, u32),
+  FadeTo((u8, u8, u8, u8), u32),
+}
+
+// Red, green, blue and alpha of a fade
+type FadeColor = (u8, u8, u8, u8);
+
+#[derive(Debug, Clone, Default)]
+pub struct CameraEffects {
+  trauma: f32,
+  ticks: u32,
+  zoom_punch: Option<(f32, u32, u32)>,
+  pan: Option<((f32, f32), u32, u32)>,
+  fade: Option<(FadeColor, FadeColor, u32, u32)>,
+  // Effects triggered last tick, so a trigger that is still overlapped doesn't restart them
+  triggered: Vec<CameraEffect>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Camera {
   pub position: (f32, f32),
   pub scale: (f32, f32),
   pub dimensions: (u16, u16),
+  #[serde(skip)]
+  pub effects: CameraEffects,
 }
 impl Camera {
   pub fn new(dimensions: (u16, u16)) -> Self {
-    Self { position: (0.0, 0.0), scale: (1.0, 1.0), dimensions }
+    Self { position: (0.0, 0.0), scale: (1.0, 1.0), dimensions, effects: CameraEffects::default() }
+  }
+  pub fn shake(&mut self, trauma: f32) {
+    self.effects.trauma = (self.effects.trauma + trauma).clamp(0.0, 1.0);
+  }
+  pub fn zoom_punch(&mut self, amount: f32, duration: u32) {
+    self.effects.zoom_punch = Some((amount, 0, duration.max(1)));
+  }
+  pub fn pan_to(&mut self, point: (f32, f32), duration: u32) {
+    if let Some((target, _, _)) = self.effects.pan {
+      if target == point {
+        return;
+      }
+    }
+    self.effects.pan = Some((point, 0, duration.max(1)));
+  }
+  pub fn fade_to(&mut self, color: FadeColor, duration: u32) {
+    let from = self.fade_color().unwrap_or((color.0, color.1, color.2, 0));
+    self.effects.fade = Some((from, color, 0, duration.max(1)));
+  }
+  fn fade_color(&self) -> Option<FadeColor> {
+    self.effects.fade.map(|(from, to, tick, duration)| {
+      let progress = tick as f32 / duration as f32;
+      let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * progress) as u8;
+      (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2), mix(from.3, to.3))
+    })
+  }
+  pub fn apply_effect(&mut self, effect: &CameraEffect) {
+    match *effect {
+      CameraEffect::Shake(trauma) => self.shake(trauma),
+      CameraEffect::ZoomPunch(amount, duration) => self.zoom_punch(amount, duration),
+      CameraEffect::PanTo(x, y, duration) => self.pan_to((x, y), duration),
+      CameraEffect::FadeTo(color, duration) => self.fade_to(color, duration),
+    }
+  }
+  // Effects of event triggers only start on the tick their trigger is entered
+  pub fn apply_triggered_effects(&mut self, effects: Vec<CameraEffect>) {
+    for effect in &effects {
+      if !self.effects.triggered.contains(effect) {
+        self.apply_effect(effect);
+      }
+    }
+    self.effects.triggered = effects;
+  }
+  pub fn next_effects_state(&mut self) {
+    let effects = &mut self.effects;
+    effects.ticks = effects.ticks.wrapping_add(1);
+    effects.trauma = (effects.trauma - TRAUMA_DECAY_PER_TICK).max(0.0);
+    effects.zoom_punch = match effects.zoom_punch {
+      Some((amount, tick, duration)) if tick < duration => Some((amount, tick + 1, duration)),
+      _ => None,
+    };
+    effects.pan = match effects.pan {
+      Some((target, tick, duration)) if tick < duration => Some((target, tick + 1, duration)),
+      _ => None,
+    };
+    // A finished fade keeps its color on screen until it is faded out again
+    effects.fade = match effects.fade {
+      Some((_, (_, _, _, 0), tick, duration)) if tick >= duration => None,
+      Some((from, to, tick, duration)) => Some((from, to, (tick + 1).min(duration), duration)),
+      None => None,
+    };
+  }
+  // Camera as it is seen this tick, with shake, zoom punch and pan applied on top of the
+  // smoothed position, so `to_target` never fights the effects
+  pub fn with_effects(&self) -> Self {
+    let effects = &self.effects;
+    let mut position = self.position;
+    let mut scale = self.scale;
+
+    if let Some((target, tick, duration)) = effects.pan {
+      let ramp = (duration as f32 / 4.0).max(1.0);
+      let weight = (tick as f32 / ramp).min((duration - tick) as f32 / ramp).min(1.0);
+      let eased = weight * weight * (3.0 - 2.0 * weight);
+      position.0 += (target.0 - position.0) * eased;
+      position.1 += (target.1 - position.1) * eased;
+    }
+    if let Some((amount, tick, duration)) = effects.zoom_punch {
+      let factor = 1.0 + amount * (1.0 - tick as f32 / duration as f32);
+      scale.0 *= factor;
+      scale.1 *= factor;
+    }
+    if effects.trauma > 0.0 {
+      let shake = effects.trauma * effects.trauma;
+      let ticks = effects.ticks as f32;
+      position.0 += MAX_SHAKE_OFFSET * shake * (ticks * 0.9).sin() * (ticks * 2.3).cos() / scale.0;
+      position.1 += MAX_SHAKE_OFFSET * shake * (ticks * 1.7).sin() * (ticks * 0.6).cos() / scale.1;
+    }
+
+    Self { position, scale, dimensions: self.dimensions, effects: CameraEffects::default() }
+  }
+  pub fn draw_fade<T: RenderTarget>(&self, canvas: &mut Canvas<T>) {
+    if let Some((r, g, b, a)) = self.fade_color() {
+      if a == 0 {
+        return;
+      }
+      let original_color = canvas.draw_color();
+      let original_blend_mode = canvas.blend_mode();
+      canvas.set_blend_mode(BlendMode::Blend);
+      canvas.set_draw_color(Color { r, g, b, a });
+      canvas
+        .fill_rect(Rect::new(0, 0, self.dimensions.0 as u32, self.dimensions.1 as u32))
+        .unwrap();
+      canvas.set_blend_mode(original_blend_mode);
+      canvas.set_draw_color(original_color);
+    }
   }
   fn restrict_zoom(&mut self) {
     if self.scale.0 > 25.0 {
@@ -44,7 +176,12 @@ impl Camera {
     self.scale.0 += (target_camera.scale.0 - self.scale.0) * rate.0;
     self.scale.1 += (target_camera.scale.1 - self.scale.1) * rate.1;
   }
-  pub fn draw_relatively(&self, canvas: &mut WindowCanvas, entity: &Entity, texture: &Texture) {
+  pub fn draw_relatively<T: RenderTarget>(
+    &self,
+    canvas: &mut Canvas<T>,
+    entity: &Entity,
+    texture: &Texture,
+  ) {
     let (_x, _y, _width, _height) = entity.to_canvas_coordinates(
       self,
       ((self.dimensions.0 / 2) as u32, (self.dimensions.1 / 2) as u32),
@@ -79,3 +216,52 @@ impl Camera {
     }
   }
 }
+
+#[test]
+fn camera_effects_do_not_move_the_smoothed_camera() {
+  let mut camera = Camera::new((900, 600));
+  camera.position = (100.0, 50.0);
+  camera.shake(1.0);
+  camera.zoom_punch(0.5, 10);
+  camera.next_effects_state();
+
+  let camera_with_effects = camera.with_effects();
+
+  assert_ne!(camera_with_effects.position, camera.position);
+  assert!(camera_with_effects.scale.0 > camera.scale.0);
+  assert_eq!(camera.position, (100.0, 50.0));
+  assert_eq!(camera.scale, (1.0, 1.0));
+}
+
+#[test]
+fn camera_effects_wear_off() {
+  let mut camera = Camera::new((900, 600));
+  camera.position = (100.0, 50.0);
+  camera.shake(1.0);
+  camera.zoom_punch(0.5, 10);
+  camera.pan_to((500.0, 500.0), 20);
+  for _ in 0..60 {
+    camera.next_effects_state();
+  }
+
+  let camera_with_effects = camera.with_effects();
+
+  assert_eq!(camera_with_effects.position, (100.0, 50.0));
+  assert_eq!(camera_with_effects.scale, (1.0, 1.0));
+}
+
+#[test]
+fn triggered_effects_only_start_when_the_trigger_is_entered() {
+  let mut camera = Camera::new((900, 600));
+  let zoom_punch = CameraEffect::ZoomPunch(0.5, 10);
+  camera.apply_triggered_effects(vec![zoom_punch.clone()]);
+  for _ in 0..20 {
+    camera.next_effects_state();
+    camera.apply_triggered_effects(vec![zoom_punch.clone()]);
+  }
+  assert_eq!(camera.with_effects().scale, (1.0, 1.0));
+
+  camera.apply_triggered_effects(vec![]);
+  camera.apply_triggered_effects(vec![zoom_punch]);
+  assert!(camera.with_effects().scale.0 > 1.0);
+}
@@ -0,0 +1,100 @@
+use super::camera::Camera;
+use super::entity::EventType;
+use super::level::Level;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, RenderTarget};
+use sdl2::surface::Surface;
+use std::fs;
+use std::path::Path;
+
+static BACKGROUND_COLOR: Color = Color { r: 42, g: 43, b: 37, a: 0xff };
+
+pub fn read_surface<T: RenderTarget>(
+  canvas: &Canvas<T>,
+  dimensions: (u32, u32),
+) -> Surface<'static> {
+  let pixels = canvas
+    .read_pixels(Rect::new(0, 0, dimensions.0, dimensions.1), PixelFormatEnum::ARGB8888)
+    .unwrap();
+  let mut surface = Surface::new(dimensions.0, dimensions.1, PixelFormatEnum::ARGB8888).unwrap();
+  let pitch = surface.pitch() as usize;
+  surface.with_lock_mut(|surface_pixels| {
+    for row in 0..dimensions.1 as usize {
+      let row_length = dimensions.0 as usize * 4;
+      surface_pixels[row * pitch..row * pitch + row_length]
+        .copy_from_slice(&pixels[row * row_length..(row + 1) * row_length]);
+    }
+  });
+  surface
+}
+
+pub fn save_screenshot<T: RenderTarget>(
+  canvas: &Canvas<T>,
+  dimensions: (u32, u32),
+  file_path: &str,
+) {
+  if let Some(directory) = Path::new(file_path).parent() {
+    fs::create_dir_all(directory).unwrap();
+  }
+  match read_surface(canvas, dimensions).save_bmp(Path::new(file_path)) {
+    Err(error) => println!("couldn't save screenshot {}: {}", file_path, error),
+    Ok(_) => println!("Saving screenshot now: {}", file_path),
+  }
+}
+
+// Simulates the level without player input through SDL's software renderer, so no window
+// or GPU is needed, and writes every frame as a numbered BMP into `output_directory`
+pub fn render_frames(
+  level_name: &str,
+  sprite_sheet_name: &str,
+  frame_count: u32,
+  dimensions: (u16, u16),
+  output_directory: &str,
+) {
+  let mut level = Level::load(level_name);
+
+  let surface =
+    Surface::new(dimensions.0 as u32, dimensions.1 as u32, PixelFormatEnum::ARGB8888).unwrap();
+  let mut canvas = surface.into_canvas().unwrap();
+  let texture_creator = canvas.texture_creator();
+  let entity_texture = {
+    let mut texture_surface =
+      Surface::load_bmp(Path::new(&format!("assets/spritesheets/{}.bmp", sprite_sheet_name)))
+        .unwrap();
+    texture_surface.set_color_key(true, Color { r: 0, g: 0, b: 0, a: 0xff }).unwrap();
+    texture_creator.create_texture_from_surface(&texture_surface).unwrap()
+  };
+
+  let mut camera = Camera::new(dimensions);
+
+  for frame in 0..frame_count {
+    let entities =
+      [&level.indestructible[..], &level.destructible[..], &level.enemies[..]].concat();
+    level.next_state(&entities);
+    for triggered_event in level.take_triggered_events() {
+      if let EventType::CameraEffect(camera_effect) = triggered_event {
+        camera.apply_effect(&camera_effect);
+      }
+    }
+    camera.next_effects_state();
+
+    if let Some(main_character) = level.main_character.first() {
+      camera.position = (
+        main_character.position.0 + main_character.dimensions.0 as f32 / 2.0,
+        main_character.position.1 + main_character.dimensions.1 as f32 / 2.0,
+      );
+    }
+
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.clear();
+    level.draw(&mut camera, &mut canvas, &entity_texture);
+    camera.draw_fade(&mut canvas);
+
+    save_screenshot(
+      &canvas,
+      (dimensions.0 as u32, dimensions.1 as u32),
+      &format!("{}/frame_{:05}.bmp", output_directory, frame),
+    );
+  }
+}
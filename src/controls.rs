@@ -1,41 +1,259 @@
+use super::settings::write_config;
+use sdl2::controller::{Axis, Button};
 use sdl2::keyboard::Keycode;
-use serde::Deserialize;
+use sdl2::mouse::MouseButton;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::mem;
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+  Jump,
+  Left,
+  Right,
+  Up,
+  Down,
+  Sprint,
+  Shoot,
+  ZoomIn,
+  ZoomOut,
+  Pause,
+  EditMode,
+  DebugOverlay,
+  Profiler,
+  ProfilerDump,
+  Screenshot,
+  Confirm,
+  FreeCamera,
+  SaveLevel,
+  Undo,
+  Redo,
+  NudgeLeft,
+  NudgeRight,
+  NudgeUp,
+  NudgeDown,
+  Snapping,
+  SpritePicker,
+}
+
+// Every action in the order the settings screen lists them
+pub static ACTIONS: [Action; 26] = [
+  Action::Jump,
+  Action::Left,
+  Action::Right,
+  Action::Up,
+  Action::Down,
+  Action::Sprint,
+  Action::Shoot,
+  Action::ZoomIn,
+  Action::ZoomOut,
+  Action::Pause,
+  Action::EditMode,
+  Action::DebugOverlay,
+  Action::Profiler,
+  Action::ProfilerDump,
+  Action::Screenshot,
+  Action::Confirm,
+  Action::FreeCamera,
+  Action::SaveLevel,
+  Action::Undo,
+  Action::Redo,
+  Action::NudgeLeft,
+  Action::NudgeRight,
+  Action::NudgeUp,
+  Action::NudgeDown,
+  Action::Snapping,
+  Action::SpritePicker,
+];
+
+impl Action {
+  pub fn string_id(self) -> &'static str {
+    match self {
+      Action::Jump => "action.jump",
+      Action::Left => "action.left",
+      Action::Right => "action.right",
+      Action::Up => "action.up",
+      Action::Down => "action.down",
+      Action::Sprint => "action.sprint",
+      Action::Shoot => "action.shoot",
+      Action::ZoomIn => "action.zoom_in",
+      Action::ZoomOut => "action.zoom_out",
+      Action::Pause => "action.pause",
+      Action::EditMode => "action.edit_mode",
+      Action::DebugOverlay => "action.debug_overlay",
+      Action::Profiler => "action.profiler",
+      Action::ProfilerDump => "action.profiler_dump",
+      Action::Screenshot => "action.screenshot",
+      Action::Confirm => "action.confirm",
+      Action::FreeCamera => "action.free_camera",
+      Action::SaveLevel => "action.save_level",
+      Action::Undo => "action.undo",
+      Action::Redo => "action.redo",
+      Action::NudgeLeft => "action.nudge_left",
+      Action::NudgeRight => "action.nudge_right",
+      Action::NudgeUp => "action.nudge_up",
+      Action::NudgeDown => "action.nudge_down",
+      Action::Snapping => "action.snapping",
+      Action::SpritePicker => "action.sprite_picker",
+    }
+  }
+  // Menu actions only fire while gameplay is frozen, so they may share bindings with gameplay
+  fn can_share_bindings(self, other: Action) -> bool {
+    let is_gameplay = |action| {
+      matches!(
+        action,
+        Action::Jump | Action::Sprint | Action::Shoot | Action::ZoomIn | Action::ZoomOut
+      )
+    };
+    (self == Action::Confirm && is_gameplay(other))
+      || (other == Action::Confirm && is_gameplay(self))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Modifier {
+  Ctrl,
+  Shift,
+  Alt,
+}
+
+impl Modifier {
+  // The modifier a key sets while it is held, if any
+  pub fn of_key(key: Keycode) -> Option<Self> {
+    match key {
+      Keycode::LCtrl | Keycode::RCtrl => Some(Modifier::Ctrl),
+      Keycode::LShift | Keycode::RShift => Some(Modifier::Shift),
+      Keycode::LAlt | Keycode::RAlt => Some(Modifier::Alt),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisDirection {
+  Negative,
+  Positive,
+}
+
+// A key binding with modifiers is a chord like Ctrl+S, axes count as one button per direction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+  Key {
+    #[serde(with = "KeycodeRef")]
+    key: Keycode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    modifiers: Vec<Modifier>,
+  },
+  MouseButton(#[serde(with = "MouseButtonRef")] MouseButton),
+  GamepadButton(#[serde(with = "ButtonRef")] Button),
+  GamepadAxis {
+    #[serde(with = "AxisRef")]
+    axis: Axis,
+    direction: AxisDirection,
+  },
+}
+
+impl Binding {
+  pub fn name(&self) -> String {
+    match self {
+      Binding::Key { key, modifiers } => modifiers
+        .iter()
+        .map(|modifier| format!("{:?}+", modifier))
+        .chain(std::iter::once(key.name()))
+        .collect(),
+      Binding::MouseButton(button) => format!("Mouse {:?}", button),
+      Binding::GamepadButton(button) => format!("Pad {}", button.string()),
+      Binding::GamepadAxis { axis, direction } => format!(
+        "Pad {}{}",
+        axis.string(),
+        if *direction == AxisDirection::Positive { "+" } else { "-" }
+      ),
+    }
+  }
+  // Rebinding replaces the bindings of the same device kind and keeps the others
+  fn is_same_kind(&self, other: &Binding) -> bool {
+    let is_gamepad = |binding: &Binding| {
+      matches!(binding, Binding::GamepadButton(_) | Binding::GamepadAxis { .. })
+    };
+    mem::discriminant(self) == mem::discriminant(other) || (is_gamepad(self) && is_gamepad(other))
+  }
+}
+
+// Stick and trigger values range from -32768 to 32767, deadzones are in the same unit
+#[derive(Serialize, Deserialize)]
+pub struct GamepadSettings {
+  pub stick_deadzone: i16,
+  pub aim_deadzone: i16,
+  pub rumble_strength: f32,
+  pub rumble_millis: u32,
+}
+
+impl Default for GamepadSettings {
+  fn default() -> Self {
+    Self { stick_deadzone: 8000, aim_deadzone: 12000, rumble_strength: 0.5, rumble_millis: 200 }
+  }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Controls {
-  #[serde(with = "KeycodeRef")]
-  pub jump_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub left_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub right_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub up_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub down_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub sprint_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub shoot_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub zoom_in_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub zoom_out_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub pause_key: Keycode,
-  #[serde(with = "KeycodeRef")]
-  pub edit_mode_key: Keycode,
+  bindings: BTreeMap<Action, Vec<Binding>>,
+  #[serde(default)]
+  pub gamepad: GamepadSettings,
+  // The cursor aims instead of rotating the aim with up and down
+  #[serde(default)]
+  pub mouse_aim: bool,
 }
 
 impl Controls {
   pub fn deserialize(serialized: String) -> Self {
     serde_json::from_str(&serialized).unwrap()
   }
+  pub fn try_load(file_path: &str) -> Result<Self, String> {
+    let serialized = std::fs::read_to_string(file_path).map_err(|error| error.to_string())?;
+    serde_json::from_str(&serialized).map_err(|error| error.to_string())
+  }
+  pub fn bindings(&self, action: Action) -> &[Binding] {
+    self.bindings.get(&action).map_or(&[], |bindings| bindings)
+  }
+  // The first binding stands for the action in hints
+  pub fn binding_name(&self, action: Action) -> String {
+    self.bindings(action).first().map_or(String::new(), |binding| binding.name())
+  }
+  pub fn rebind(&mut self, action: Action, binding: Binding) {
+    let bindings = self.bindings.entry(action).or_default();
+    bindings.retain(|bound| !bound.is_same_kind(&binding));
+    bindings.push(binding);
+  }
+  // Other actions with one of the bindings of `action`
+  pub fn sharing_bindings(&self, action: Action) -> Vec<Action> {
+    ACTIONS
+      .iter()
+      .filter(|other| {
+        **other != action
+          && self.bindings(**other).iter().any(|binding| self.bindings(action).contains(binding))
+      })
+      .cloned()
+      .collect()
+  }
+  // Actions sharing a binding with `action` that could fire in the same state
+  pub fn conflicts(&self, action: Action) -> Vec<Action> {
+    self
+      .sharing_bindings(action)
+      .into_iter()
+      .filter(|other| !action.can_share_bindings(*other))
+      .collect()
+  }
+  pub fn has_conflicts(&self) -> bool {
+    ACTIONS.iter().any(|action| !self.conflicts(*action).is_empty())
+  }
+  pub fn save(&self, file_path: &str) {
+    write_config(file_path, self);
+  }
 }
 
 use sdl2::sys;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(remote = "Keycode")]
 #[repr(i32)]
 enum KeycodeRef {
@@ -275,3 +493,65 @@ enum KeycodeRef {
   Eject = sys::SDLK_EJECT as i32,
   Sleep = sys::SDLK_SLEEP as i32,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MouseButton")]
+enum MouseButtonRef {
+  Unknown,
+  Left,
+  Middle,
+  Right,
+  X1,
+  X2,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Button")]
+enum ButtonRef {
+  A,
+  B,
+  X,
+  Y,
+  Back,
+  Guide,
+  Start,
+  LeftStick,
+  RightStick,
+  LeftShoulder,
+  RightShoulder,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Axis")]
+enum AxisRef {
+  LeftX,
+  LeftY,
+  RightX,
+  RightY,
+  TriggerLeft,
+  TriggerRight,
+}
+
+#[test]
+fn controls_detect_conflicting_bindings() {
+  let mut controls =
+    Controls::deserialize(std::fs::read_to_string("config/controls.json").unwrap());
+  assert!(!controls.has_conflicts());
+
+  let shoot_key = controls.bindings(Action::Shoot)[0].clone();
+  controls.rebind(Action::Jump, shoot_key);
+  assert_eq!(controls.conflicts(Action::Jump), vec![Action::Shoot]);
+  assert!(controls.has_conflicts());
+
+  let save_level = &controls.bindings(Action::SaveLevel)[0];
+  assert_eq!(save_level.name(), "Ctrl+S");
+  assert!(!controls.bindings(Action::Down).contains(save_level));
+
+  // Confirm and jump share the A button, but never fire in the same state
+  assert!(controls.conflicts(Action::Confirm).is_empty());
+  assert!(controls.sharing_bindings(Action::Confirm).contains(&Action::Jump));
+}
@@ -0,0 +1,114 @@
+use super::camera::Camera;
+use super::entity::{CollisionSide, Entity};
+use super::level::Level;
+use super::text::{show_text_line, Font};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+
+static VELOCITY_VECTOR_LENGTH: f32 = 5.0;
+
+static COLLISION_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static GROUND_CONTACT_COLOR: Color = Color { r: 255, g: 230, b: 0, a: 0xff };
+static EVENT_LINK_COLOR: Color = Color { r: 255, g: 0, b: 255, a: 0xff };
+static VELOCITY_COLOR: Color = Color { r: 0, g: 255, b: 255, a: 0xff };
+
+fn containers(level: &Level) -> Vec<(&Vec<Entity>, Color)> {
+  vec![
+    (&level.background, Color { r: 90, g: 90, b: 200, a: 0xff }),
+    (&level.indestructible, Color { r: 120, g: 120, b: 120, a: 0xff }),
+    (&level.destructible, Color { r: 200, g: 140, b: 60, a: 0xff }),
+    (&level.enemies, Color { r: 230, g: 40, b: 40, a: 0xff }),
+    (&level.main_character, Color { r: 40, g: 230, b: 40, a: 0xff }),
+    (&level.effects, Color { r: 250, g: 250, b: 120, a: 0xff }),
+    (&level.foreground, Color { r: 160, g: 90, b: 200, a: 0xff }),
+  ]
+}
+
+fn canvas_rect(entity: &Entity, camera: &Camera) -> Rect {
+  let (x, y, width, height) = entity.to_canvas_coordinates(
+    camera,
+    ((camera.dimensions.0 / 2) as u32, (camera.dimensions.1 / 2) as u32),
+  );
+  Rect::new(x as i32, y as i32, width.max(1), height.max(1))
+}
+
+fn side_line(rect: Rect, side: CollisionSide) -> (Point, Point) {
+  match side {
+    CollisionSide::Top => (rect.top_left(), Point::new(rect.right(), rect.top())),
+    CollisionSide::Bottom => (Point::new(rect.left(), rect.bottom()), rect.bottom_right()),
+    CollisionSide::Left => (rect.top_left(), Point::new(rect.left(), rect.bottom())),
+    CollisionSide::Right => (Point::new(rect.right(), rect.top()), rect.bottom_right()),
+  }
+}
+
+pub fn draw_debug_overlay(
+  canvas: &mut WindowCanvas,
+  font: &mut Font,
+  level: &Level,
+  camera: &Camera,
+  interactive_entities: &Vec<Entity>,
+) {
+  let original_color = canvas.draw_color();
+  let camera = &camera.with_effects();
+  let all_entities =
+    containers(level).into_iter().flat_map(|(container, _)| container.iter()).collect::<Vec<_>>();
+
+  for (container, color) in containers(level) {
+    for entity in container {
+      let rect = canvas_rect(entity, camera);
+
+      canvas.set_draw_color(color);
+      canvas.draw_rect(rect).unwrap();
+
+      canvas.set_draw_color(COLLISION_COLOR);
+      for side in &entity.collision_sides {
+        let (start, end) = side_line(rect, *side);
+        canvas.draw_line(start, end).unwrap();
+      }
+
+      if entity.velocity != (0.0, 0.0) {
+        canvas.set_draw_color(VELOCITY_COLOR);
+        canvas
+          .draw_line(
+            rect.center(),
+            rect.center().offset(
+              (entity.velocity.0 * camera.scale.0 * VELOCITY_VECTOR_LENGTH) as i32,
+              (entity.velocity.1 * camera.scale.1 * VELOCITY_VECTOR_LENGTH) as i32,
+            ),
+          )
+          .unwrap();
+
+        if let Some(ground_entity) = entity.find_ground_entity(interactive_entities) {
+          let ground_rect = canvas_rect(ground_entity, camera);
+          canvas.set_draw_color(GROUND_CONTACT_COLOR);
+          canvas.draw_rect(ground_rect).unwrap();
+          canvas
+            .draw_line(Point::new(rect.center().x(), rect.bottom()), ground_rect.center())
+            .unwrap();
+        }
+      }
+
+      if let Some(event) = &entity.event {
+        canvas.set_draw_color(EVENT_LINK_COLOR);
+        for receiving_entity in
+          all_entities.iter().filter(|receiving_entity| match &receiving_entity.id {
+            Some(id) => event.receiving_entity_ids.contains(id),
+            None => false,
+          })
+        {
+          canvas.draw_line(rect.center(), canvas_rect(receiving_entity, camera).center()).unwrap();
+        }
+      }
+
+      let label = match (&entity.id, entity.health) {
+        (Some(id), Some(health)) => format!("{} {}", id, health),
+        (Some(id), None) => id.clone(),
+        (None, Some(health)) => format!("{}", health),
+        (None, None) => continue,
+      };
+      show_text_line(canvas, font, &label, (rect.x(), rect.y() - 10), 1, 1.1, color);
+    }
+  }
+  canvas.set_draw_color(original_color);
+}
@@ -0,0 +1,356 @@
+use super::localization::Localization;
+use super::text::{
+  show_text, show_text_revealed, visible_length, Font, FontDescriptor, TextLayout,
+};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, WindowCanvas};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+static REVEALED_CHARACTERS_PER_TICK: f32 = 0.7;
+
+static BOX_MARGIN: i32 = 16;
+static BOX_PADDING: i32 = 10;
+static BOX_HEIGHT: u32 = 130;
+static PORTRAIT_SIZE: u32 = 64;
+static LINE_SPACING: i32 = 4;
+
+static BOX_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static BORDER_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static TEXT_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static SPEAKER_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static CHOICE_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+
+#[derive(Debug, Deserialize)]
+pub struct DialogChoice {
+  pub text: String,
+  pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DialogNode {
+  pub speaker: Option<String>,
+  pub portrait: Option<(i32, i32, u32, u32)>,
+  pub pages: Vec<String>,
+  #[serde(default = "default_choices")]
+  pub choices: Vec<DialogChoice>,
+  pub next: Option<String>,
+}
+
+fn default_choices() -> Vec<DialogChoice> {
+  vec![]
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DialogScript {
+  pub start: String,
+  pub nodes: HashMap<String, DialogNode>,
+}
+
+impl DialogScript {
+  // Every node a dialog can go to has to exist and show at least one page, so a typo in a
+  // script is found when it is loaded and not in the middle of the conversation
+  pub fn deserialize(serialized: String) -> Result<Self, String> {
+    let script: Self = serde_json::from_str(&serialized).map_err(|error| error.to_string())?;
+    let mut names = script.nodes.keys().collect::<Vec<_>>();
+    names.sort();
+    let check_node = |name: &String| {
+      if script.nodes.contains_key(name) {
+        Ok(())
+      } else {
+        Err(format!("no node {:?}", name))
+      }
+    };
+    check_node(&script.start)?;
+    for name in names {
+      let node = &script.nodes[name];
+      if node.pages.is_empty() {
+        return Err(format!("node {:?} has no pages", name));
+      }
+      node.next.iter().try_for_each(check_node)?;
+      node.choices.iter().filter_map(|choice| choice.next.as_ref()).try_for_each(check_node)?;
+    }
+    Ok(script)
+  }
+  pub fn path(name: &str) -> String {
+    format!("assets/dialogs/{}.json", name)
+  }
+  pub fn read(name: &str) -> Result<Self, String> {
+    Self::deserialize(fs::read_to_string(Self::path(name)).map_err(|error| error.to_string())?)
+  }
+  // Texts in dialog files are string ids, they are resolved once when the dialog is loaded
+  pub fn load(name: &str, localization: &Localization) -> Result<Self, String> {
+    let mut script = Self::read(name)?;
+    script.localize(localization);
+    Ok(script)
+  }
+  pub fn localize(&mut self, localization: &Localization) {
+    for node in self.nodes.values_mut() {
+      if let Some(speaker) = &mut node.speaker {
+        *speaker = localization.get(speaker).to_string();
+      }
+      for page in &mut node.pages {
+        *page = localization.get(page).to_string();
+      }
+      for choice in &mut node.choices {
+        choice.text = localization.get(&choice.text).to_string();
+      }
+    }
+  }
+}
+
+pub struct Dialog {
+  script: DialogScript,
+  node: String,
+  page: usize,
+  revealed_characters: f32,
+  selected_choice: usize,
+  // Laid-out glyphs on every page of every node, spaces dropped at line breaks aren't revealed
+  page_lengths: HashMap<String, Vec<usize>>,
+}
+
+fn box_rect(dimensions: (u16, u16)) -> Rect {
+  Rect::new(
+    BOX_MARGIN,
+    dimensions.1 as i32 - BOX_HEIGHT as i32 - BOX_MARGIN,
+    dimensions.0 as u32 - BOX_MARGIN as u32 * 2,
+    BOX_HEIGHT,
+  )
+}
+
+// Left edge and layout of the page text, which moves right to make room for a portrait
+fn text_layout(node: &DialogNode, box_rect: Rect) -> (i32, TextLayout) {
+  let mut text_x = box_rect.x() + BOX_PADDING;
+  if node.portrait.is_some() {
+    text_x += PORTRAIT_SIZE as i32 + BOX_PADDING;
+  }
+  let text_width = (box_rect.x() + box_rect.width() as i32 - BOX_PADDING - text_x).max(1) as u32;
+  (text_x, TextLayout::new(2).box_width(text_width).line_spacing(LINE_SPACING))
+}
+
+impl Dialog {
+  pub fn new(script: DialogScript, descriptor: &FontDescriptor, dimensions: (u16, u16)) -> Self {
+    let box_rect = box_rect(dimensions);
+    let page_lengths = script
+      .nodes
+      .iter()
+      .map(|(name, node)| {
+        let (_, layout) = text_layout(node, box_rect);
+        let lengths = node.pages.iter().map(|page| visible_length(descriptor, page, &layout));
+        (name.clone(), lengths.collect())
+      })
+      .collect();
+    Self {
+      node: script.start.clone(),
+      script,
+      page: 0,
+      revealed_characters: 0.0,
+      selected_choice: 0,
+      page_lengths,
+    }
+  }
+  fn current_node(&self) -> &DialogNode {
+    &self.script.nodes[&self.node]
+  }
+  fn current_page(&self) -> &str {
+    &self.current_node().pages[self.page]
+  }
+  fn page_length(&self) -> usize {
+    self.page_lengths[&self.node][self.page]
+  }
+  fn is_page_revealed(&self) -> bool {
+    self.revealed_characters as usize >= self.page_length()
+  }
+  fn is_showing_choices(&self) -> bool {
+    self.is_page_revealed()
+      && self.page + 1 == self.current_node().pages.len()
+      && !self.current_node().choices.is_empty()
+  }
+  pub fn next_state(&mut self) {
+    self.revealed_characters += REVEALED_CHARACTERS_PER_TICK;
+  }
+  pub fn select_previous_choice(&mut self) {
+    if self.is_showing_choices() && self.selected_choice > 0 {
+      self.selected_choice -= 1;
+    }
+  }
+  pub fn select_next_choice(&mut self) {
+    if self.is_showing_choices() && self.selected_choice + 1 < self.current_node().choices.len() {
+      self.selected_choice += 1;
+    }
+  }
+  fn go_to(&mut self, node: Option<String>) -> bool {
+    match node {
+      Some(node) => {
+        self.node = node;
+        self.page = 0;
+        self.revealed_characters = 0.0;
+        self.selected_choice = 0;
+        true
+      }
+      None => false,
+    }
+  }
+  // Reveals the page, turns it or follows the selected choice. Returns false once the dialog is over
+  pub fn confirm(&mut self) -> bool {
+    if !self.is_page_revealed() {
+      self.revealed_characters = self.page_length() as f32;
+      return true;
+    }
+    if self.page + 1 < self.current_node().pages.len() {
+      self.page += 1;
+      self.revealed_characters = 0.0;
+      return true;
+    }
+    let next = match self.current_node().choices.get(self.selected_choice) {
+      Some(choice) => choice.next.clone(),
+      None => self.current_node().next.clone(),
+    };
+    self.go_to(next)
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    portrait_texture: &Texture,
+    dimensions: (u16, u16),
+  ) {
+    let box_rect = box_rect(dimensions);
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(BOX_COLOR);
+    canvas.fill_rect(box_rect).unwrap();
+    canvas.set_draw_color(BORDER_COLOR);
+    canvas.draw_rect(box_rect).unwrap();
+    canvas.set_draw_color(original_color);
+
+    let node = self.current_node();
+    if let Some((x, y, width, height)) = node.portrait {
+      canvas
+        .copy_ex(
+          portrait_texture,
+          Some(Rect::new(x, y, width, height)),
+          Some(Rect::new(
+            box_rect.x() + BOX_PADDING,
+            box_rect.y() + BOX_PADDING,
+            PORTRAIT_SIZE,
+            PORTRAIT_SIZE,
+          )),
+          0.0,
+          None,
+          false,
+          false,
+        )
+        .unwrap();
+    }
+    let (text_x, layout) = text_layout(node, box_rect);
+
+    let mut text_y = box_rect.y() + BOX_PADDING;
+    if let Some(speaker) = &node.speaker {
+      let speaker_rect =
+        show_text(canvas, font, speaker, (text_x, text_y), &TextLayout::new(2), SPEAKER_COLOR);
+      text_y += speaker_rect.height() as i32 + BOX_PADDING / 2;
+    }
+    let page_rect = show_text_revealed(
+      canvas,
+      font,
+      self.current_page(),
+      (text_x, text_y),
+      &layout,
+      TEXT_COLOR,
+      self.revealed_characters as usize,
+    );
+
+    if self.is_showing_choices() {
+      let mut choice_y = page_rect.y() + page_rect.height() as i32 + BOX_PADDING;
+      for (index, choice) in node.choices.iter().enumerate() {
+        let (prefix, color) =
+          if index == self.selected_choice { ("> ", TEXT_COLOR) } else { ("  ", CHOICE_COLOR) };
+        let choice_rect = show_text(
+          canvas,
+          font,
+          &format!("{}{}", prefix, choice.text),
+          (text_x, choice_y),
+          &layout,
+          color,
+        );
+        choice_y += choice_rect.height() as i32 + 2;
+      }
+    }
+  }
+}
+
+#[test]
+fn dialog_pages_and_choices() {
+  let script = DialogScript::deserialize(
+    r#"{
+      "start": "question",
+      "nodes": {
+        "question": { "pages": ["Hi", "Continue?"], "choices": [
+          { "text": "Yes", "next": "answer" },
+          { "text": "No", "next": null }
+        ] },
+        "answer": { "speaker": "Sign", "pages": ["Onwards!"], "next": null }
+      }
+    }"#
+      .to_string(),
+  )
+  .unwrap();
+  let descriptor =
+    FontDescriptor::deserialize(fs::read_to_string("assets/fonts/default.json").unwrap()).unwrap();
+  let mut dialog = Dialog::new(script, &descriptor, (320, 200));
+
+  assert!(dialog.confirm());
+  assert_eq!(dialog.current_page(), "Hi");
+  assert!(dialog.confirm());
+  assert_eq!(dialog.current_page(), "Continue?");
+  assert!(dialog.confirm());
+  dialog.select_next_choice();
+  dialog.select_previous_choice();
+  assert!(dialog.confirm());
+  assert_eq!(dialog.current_page(), "Onwards!");
+  assert!(dialog.confirm());
+  assert!(!dialog.confirm());
+}
+
+#[test]
+fn dialog_scripts_with_missing_nodes_or_pages_are_rejected() {
+  let script = |start: &str, next: &str, pages: &str| {
+    DialogScript::deserialize(format!(
+      r#"{{
+        "start": "{}",
+        "nodes": {{
+          "greeting": {{ "pages": {}, "choices": [{{ "text": "Bye", "next": "{}" }}] }}
+        }}
+      }}"#,
+      start, pages, next
+    ))
+  };
+
+  assert!(script("greeting", "greeting", r#"["Hi"]"#).is_ok());
+  assert_eq!(script("greting", "greeting", r#"["Hi"]"#).err().unwrap(), r#"no node "greting""#);
+  assert_eq!(script("greeting", "farewell", r#"["Hi"]"#).err().unwrap(), r#"no node "farewell""#);
+  assert_eq!(
+    script("greeting", "greeting", "[]").err().unwrap(),
+    r#"node "greeting" has no pages"#
+  );
+}
+
+#[test]
+fn wrapped_pages_are_revealed_once_their_glyphs_are() {
+  let script = DialogScript::deserialize(
+    r#"{ "start": "sign", "nodes": { "sign": { "pages": ["aaaaaaaaaa aaaaaaaaaa"], "next": null } } }"#
+      .to_string(),
+  )
+  .unwrap();
+  let descriptor =
+    FontDescriptor::deserialize(fs::read_to_string("assets/fonts/default.json").unwrap()).unwrap();
+  let mut dialog = Dialog::new(script, &descriptor, (320, 200));
+
+  // The space at the line break isn't drawn, so it isn't waited for either
+  assert_eq!(dialog.page_length(), 20);
+  dialog.revealed_characters = 20.0;
+  assert!(dialog.is_page_revealed());
+  assert!(!dialog.confirm());
+}
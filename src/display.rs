@@ -0,0 +1,93 @@
+use super::settings::write_config;
+use sdl2::rect::Rect;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScalingMode {
+  // Largest whole-number scale that fits the window, keeps pixel art crisp
+  Integer,
+  // Largest scale that fits the window while keeping the aspect ratio
+  BestFit,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Display {
+  pub logical_resolution: (u16, u16),
+  pub scaling_mode: ScalingMode,
+  #[serde(default)]
+  pub fullscreen: bool,
+}
+
+impl Display {
+  pub fn deserialize(serialized: String) -> Self {
+    serde_json::from_str(&serialized).unwrap()
+  }
+  pub fn save(&self, file_path: &str) {
+    write_config(file_path, self);
+  }
+  pub fn scale(&self, window_size: (u32, u32)) -> f32 {
+    let scale = (window_size.0 as f32 / self.logical_resolution.0 as f32)
+      .min(window_size.1 as f32 / self.logical_resolution.1 as f32);
+    match self.scaling_mode {
+      ScalingMode::Integer if scale >= 1.0 => scale.floor(),
+      _ => scale,
+    }
+  }
+  // Area of the window the logical frame is scaled into, the rest is letterboxing
+  pub fn viewport(&self, window_size: (u32, u32)) -> Rect {
+    let scale = self.scale(window_size);
+    let width = (self.logical_resolution.0 as f32 * scale) as u32;
+    let height = (self.logical_resolution.1 as f32 * scale) as u32;
+    Rect::new(
+      (window_size.0 as i32 - width as i32) / 2,
+      (window_size.1 as i32 - height as i32) / 2,
+      width,
+      height,
+    )
+  }
+  pub fn window_to_logical(&self, (x, y): (i32, i32), window_size: (u32, u32)) -> (i32, i32) {
+    let viewport = self.viewport(window_size);
+    let scale = self.scale(window_size);
+    (((x - viewport.x()) as f32 / scale) as i32, ((y - viewport.y()) as f32 / scale) as i32)
+  }
+}
+
+#[test]
+fn integer_scaling_letterboxes_the_remaining_space() {
+  let display = Display {
+    logical_resolution: (300, 200),
+    scaling_mode: ScalingMode::Integer,
+    fullscreen: false,
+  };
+
+  let viewport = display.viewport((1000, 700));
+
+  assert_eq!(display.scale((1000, 700)), 3.0);
+  assert_eq!(viewport, Rect::new(50, 50, 900, 600));
+}
+
+#[test]
+fn best_fit_scaling_fills_one_axis() {
+  let display = Display {
+    logical_resolution: (300, 200),
+    scaling_mode: ScalingMode::BestFit,
+    fullscreen: false,
+  };
+
+  let viewport = display.viewport((1000, 700));
+
+  assert_eq!(viewport, Rect::new(0, 17, 1000, 666));
+}
+
+#[test]
+fn window_to_logical_coordinates_ignore_letterboxing() {
+  let display = Display {
+    logical_resolution: (300, 200),
+    scaling_mode: ScalingMode::Integer,
+    fullscreen: false,
+  };
+
+  assert_eq!(display.window_to_logical((50, 50), (1000, 700)), (0, 0));
+  assert_eq!(display.window_to_logical((500, 350), (1000, 700)), (150, 100));
+  assert_eq!(display.window_to_logical((949, 649), (1000, 700)), (299, 199));
+}
@@ -0,0 +1,160 @@
+use super::editor_menu::LevelEntityVariant;
+use super::entity::Entity;
+use super::level::Level;
+use std::collections::VecDeque;
+
+// One undoable editor operation. Entities are addressed by layer and index, so the history
+// only holds while nothing but the editor changes the level. Play-testing gives the edited level
+// back when returning to the editor.
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+  // Entities appended to the end of a layer
+  Create { layer: LevelEntityVariant, entities: Vec<Entity> },
+  // Removed entities with their former index, ascending within each layer
+  Delete { entities: Vec<(LevelEntityVariant, usize, Entity)> },
+  // Entities replaced in place, with their state before and after
+  Change { entities: Vec<(LevelEntityVariant, usize, Entity, Entity)> },
+}
+
+impl EditCommand {
+  fn apply(&self, level: &mut Level) {
+    match self {
+      EditCommand::Create { layer, entities } => {
+        if let Some(layer_entities) = layer.layer_mut(level) {
+          layer_entities.extend(entities.iter().cloned());
+        }
+      }
+      EditCommand::Delete { entities } => {
+        for (layer, index, _) in entities.iter().rev() {
+          if let Some(layer_entities) = layer.layer_mut(level) {
+            layer_entities.remove(*index);
+          }
+        }
+      }
+      EditCommand::Change { entities } => {
+        for (layer, index, _, after) in entities {
+          if let Some(layer_entities) = layer.layer_mut(level) {
+            layer_entities[*index] = after.clone();
+          }
+        }
+      }
+    }
+  }
+  fn revert(&self, level: &mut Level) {
+    match self {
+      EditCommand::Create { layer, entities } => {
+        if let Some(layer_entities) = layer.layer_mut(level) {
+          let length = layer_entities.len().saturating_sub(entities.len());
+          layer_entities.truncate(length);
+        }
+      }
+      EditCommand::Delete { entities } => {
+        for (layer, index, entity) in entities {
+          if let Some(layer_entities) = layer.layer_mut(level) {
+            layer_entities.insert(*index, entity.clone());
+          }
+        }
+      }
+      EditCommand::Change { entities } => {
+        for (layer, index, before, _) in entities {
+          if let Some(layer_entities) = layer.layer_mut(level) {
+            layer_entities[*index] = before.clone();
+          }
+        }
+      }
+    }
+  }
+}
+
+pub struct EditorHistory {
+  undo_steps: VecDeque<EditCommand>,
+  redo_steps: Vec<EditCommand>,
+  depth: usize,
+  // Placements only merge with the placement right before them
+  is_placing: bool,
+}
+
+impl EditorHistory {
+  pub fn new(depth: usize) -> Self {
+    Self { undo_steps: VecDeque::new(), redo_steps: vec![], depth, is_placing: false }
+  }
+  // Applies `command` and makes it the newest undo step. Placements in the same layer as the
+  // previous placement are added to its step.
+  pub fn execute(&mut self, level: &mut Level, command: EditCommand) {
+    command.apply(level);
+    self.redo_steps.clear();
+
+    let is_placing = self.is_placing;
+    if let EditCommand::Create { layer, entities } = &command {
+      if let Some(EditCommand::Create { layer: last_layer, entities: last_entities }) =
+        self.undo_steps.back_mut().filter(|_| is_placing)
+      {
+        if last_layer == layer {
+          last_entities.extend(entities.iter().cloned());
+          return;
+        }
+      }
+    }
+    self.is_placing = matches!(command, EditCommand::Create { .. });
+    self.undo_steps.push_back(command);
+    while self.undo_steps.len() > self.depth {
+      self.undo_steps.pop_front();
+    }
+  }
+  pub fn undo(&mut self, level: &mut Level) {
+    self.is_placing = false;
+    if let Some(command) = self.undo_steps.pop_back() {
+      command.revert(level);
+      self.redo_steps.push(command);
+    }
+  }
+  pub fn redo(&mut self, level: &mut Level) {
+    self.is_placing = false;
+    if let Some(command) = self.redo_steps.pop() {
+      command.apply(level);
+      self.undo_steps.push_back(command);
+    }
+  }
+  pub fn clear(&mut self) {
+    self.undo_steps.clear();
+    self.redo_steps.clear();
+    self.is_placing = false;
+  }
+}
+
+#[test]
+fn editor_history_merges_placements_and_restores_deletions() {
+  let mut level = Level {
+    name: None,
+    background: vec![],
+    indestructible: vec![Entity::new(0.0, 0.0, 10, 10)],
+    destructible: vec![],
+    enemies: vec![],
+    main_character: vec![],
+    effects: vec![],
+    foreground: vec![],
+  };
+  let mut history = EditorHistory::new(10);
+  let create = |x| EditCommand::Create {
+    layer: LevelEntityVariant::Indestructible,
+    entities: vec![Entity::new(x, 0.0, 10, 10)],
+  };
+
+  history.execute(&mut level, create(20.0));
+  history.execute(&mut level, create(40.0));
+  assert_eq!(level.indestructible.len(), 3);
+  history.undo(&mut level);
+  assert_eq!(level.indestructible.len(), 1);
+  history.redo(&mut level);
+  assert_eq!(level.indestructible.len(), 3);
+
+  let deleted = level.indestructible[1].clone();
+  history.execute(
+    &mut level,
+    EditCommand::Delete { entities: vec![(LevelEntityVariant::Indestructible, 1, deleted)] },
+  );
+  assert_eq!(level.indestructible[1].position.0, 40.0);
+  history.undo(&mut level);
+  assert_eq!(level.indestructible[1].position.0, 20.0);
+  assert_eq!(level.indestructible.len(), 3);
+}
@@ -1,8 +1,40 @@
+use super::camera::Camera;
+use super::editor_history::{EditCommand, EditorHistory};
+use super::editor_snapping::{draw_guides, entity_edges, Guide, GuideAxis, Snapping};
 use super::entity::Entity;
+use super::entity_inspector::EntityInspector;
 use super::level::Level;
-use sdl2::rect::Rect;
+use super::localization::Localization;
+use super::sprite_picker::{PickerClick, SpritePicker};
+use super::text::Font;
+use sdl2::event::Event;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Texture, WindowCanvas};
+use serde::Deserialize;
+use std::cmp;
 
-#[derive(Debug)]
+static HANDLE_SIZE: u32 = 8;
+static SELECTION_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static BOX_SELECT_COLOR: Color = Color { r: 120, g: 180, b: 255, a: 0xff };
+
+#[derive(Debug, Deserialize)]
+pub struct EditorConfig {
+  pub history_depth: usize,
+  pub grid_size: u32,
+  // In canvas pixels, so guides feel the same at every zoom
+  pub guide_distance: f32,
+  // What a click on the sprite picker picks
+  pub sprite_frame_size: (u32, u32),
+}
+
+impl EditorConfig {
+  pub fn deserialize(serialized: String) -> Self {
+    serde_json::from_str(&serialized).unwrap()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LevelEntityVariant {
   Background,
   Indestructible,
@@ -12,13 +44,147 @@ pub enum LevelEntityVariant {
   Effects,
   Foreground,
   Deletion,
+  Selection,
+}
+
+// Every variant that is a layer of the level, back to front
+pub static LAYERS: [LevelEntityVariant; 7] = [
+  LevelEntityVariant::Background,
+  LevelEntityVariant::Indestructible,
+  LevelEntityVariant::Destructible,
+  LevelEntityVariant::Enemies,
+  LevelEntityVariant::MainCharacter,
+  LevelEntityVariant::Effects,
+  LevelEntityVariant::Foreground,
+];
+
+impl LevelEntityVariant {
+  pub fn layer(self, level: &Level) -> Option<&Vec<Entity>> {
+    match self {
+      LevelEntityVariant::Background => Some(&level.background),
+      LevelEntityVariant::Indestructible => Some(&level.indestructible),
+      LevelEntityVariant::Destructible => Some(&level.destructible),
+      LevelEntityVariant::Enemies => Some(&level.enemies),
+      LevelEntityVariant::MainCharacter => Some(&level.main_character),
+      LevelEntityVariant::Effects => Some(&level.effects),
+      LevelEntityVariant::Foreground => Some(&level.foreground),
+      LevelEntityVariant::Deletion | LevelEntityVariant::Selection => None,
+    }
+  }
+  pub fn layer_mut(self, level: &mut Level) -> Option<&mut Vec<Entity>> {
+    match self {
+      LevelEntityVariant::Background => Some(&mut level.background),
+      LevelEntityVariant::Indestructible => Some(&mut level.indestructible),
+      LevelEntityVariant::Destructible => Some(&mut level.destructible),
+      LevelEntityVariant::Enemies => Some(&mut level.enemies),
+      LevelEntityVariant::MainCharacter => Some(&mut level.main_character),
+      LevelEntityVariant::Effects => Some(&mut level.effects),
+      LevelEntityVariant::Foreground => Some(&mut level.foreground),
+      LevelEntityVariant::Deletion | LevelEntityVariant::Selection => None,
+    }
+  }
+}
+
+// An entity in the level, addressed by its layer and its index in that layer
+pub type EntityRef = (LevelEntityVariant, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeHandle {
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+// Moves and resizes change the level live and become one undo step when the mouse is released
+enum EditorDrag {
+  Move { start: (i32, i32), originals: Vec<Entity> },
+  Resize { handle: ResizeHandle, start: (i32, i32), original: Box<Entity> },
+  BoxSelect { start: (i32, i32), end: (i32, i32) },
+}
+
+fn canvas_rect(entity: &Entity, camera: &Camera) -> Rect {
+  let offset = ((camera.dimensions.0 / 2) as u32, (camera.dimensions.1 / 2) as u32);
+  let (x, y, width, height) = entity.to_canvas_coordinates(camera, offset);
+  Rect::new(x as i32, y as i32, width.max(1), height.max(1))
+}
+
+fn handle_rects(rect: Rect) -> [(ResizeHandle, Rect); 4] {
+  let handle = |x: i32, y: i32| {
+    Rect::new(x - HANDLE_SIZE as i32 / 2, y - HANDLE_SIZE as i32 / 2, HANDLE_SIZE, HANDLE_SIZE)
+  };
+  [
+    (ResizeHandle::TopLeft, handle(rect.left(), rect.top())),
+    (ResizeHandle::TopRight, handle(rect.right(), rect.top())),
+    (ResizeHandle::BottomLeft, handle(rect.left(), rect.bottom())),
+    (ResizeHandle::BottomRight, handle(rect.right(), rect.bottom())),
+  ]
+}
+
+fn box_rect(start: (i32, i32), end: (i32, i32)) -> Rect {
+  let (x, width) = (cmp::min(start.0, end.0), (start.0 - end.0).wrapping_abs() as u32);
+  let (y, height) = (cmp::min(start.1, end.1), (start.1 - end.1).wrapping_abs() as u32);
+  Rect::new(x, y, width.max(1), height.max(1))
+}
+
+// Zoom scales canvas distances, parallax only shifts layers, so a drag is the same in every layer
+fn world_delta(start: (i32, i32), end: (i32, i32), camera: &Camera) -> (f32, f32) {
+  ((end.0 - start.0) as f32 / camera.scale.0, (end.1 - start.1) as f32 / camera.scale.1)
+}
+
+// Whether a handle moves the left and the top edge, or the right and the bottom one
+fn handle_sides(handle: ResizeHandle) -> (bool, bool) {
+  (
+    handle == ResizeHandle::TopLeft || handle == ResizeHandle::BottomLeft,
+    handle == ResizeHandle::TopLeft || handle == ResizeHandle::TopRight,
+  )
+}
+
+// Dragging an edge past the opposite one stops at a size of one
+fn resized(original: &Entity, handle: ResizeHandle, delta: (f32, f32)) -> Entity {
+  let mut entity = original.clone();
+  let (left, top) = original.position;
+  let right = left + original.dimensions.0 as f32;
+  let bottom = top + original.dimensions.1 as f32;
+  let (is_left, is_top) = handle_sides(handle);
+  if is_left {
+    entity.position.0 = (left + delta.0).min(right - 1.0);
+    entity.dimensions.0 = (right - entity.position.0) as u32;
+  } else {
+    entity.dimensions.0 = (right + delta.0 - left).max(1.0) as u32;
+  }
+  if is_top {
+    entity.position.1 = (top + delta.1).min(bottom - 1.0);
+    entity.dimensions.1 = (bottom - entity.position.1) as u32;
+  } else {
+    entity.dimensions.1 = (bottom + delta.1 - top).max(1.0) as u32;
+  }
+  entity
 }
+
 pub struct EditorMenu {
   pub variant: LevelEntityVariant,
+  pub history: EditorHistory,
+  pub selection: Vec<EntityRef>,
+  drag: Option<EditorDrag>,
+  inspector: EntityInspector,
+  pub snapping: Snapping,
+  // The edges the current drag snapped to
+  guides: Vec<Guide>,
+  pub sprite_picker: SpritePicker,
 }
 impl EditorMenu {
-  pub fn new() -> Self {
-    Self { variant: LevelEntityVariant::Effects }
+  pub fn new(config: &EditorConfig) -> Self {
+    Self {
+      variant: LevelEntityVariant::Effects,
+      history: EditorHistory::new(config.history_depth),
+      selection: vec![],
+      drag: None,
+      inspector: EntityInspector::new(),
+      snapping: Snapping::new(config.grid_size, config.guide_distance),
+      guides: vec![],
+      sprite_picker: SpritePicker::new(config.sprite_frame_size),
+    }
   }
   pub fn variant(&mut self, variant: LevelEntityVariant) {
     self.variant = variant;
@@ -33,43 +199,530 @@ impl EditorMenu {
       (LevelEntityVariant::Effects, Rect::new(0, 30 + (25 * 5), 20, 20), (20 * 6, 0, 20, 20)),
       (LevelEntityVariant::Foreground, Rect::new(0, 30 + (25 * 6), 20, 20), (20 * 7, 0, 20, 20)),
       (LevelEntityVariant::Deletion, Rect::new(0, 30 + (25 * 7), 20, 20), (20 * 8, 0, 20, 20)),
+      (LevelEntityVariant::Selection, Rect::new(0, 30 + (25 * 8), 20, 20), (20 * 12, 0, 20, 20)),
     ]
   }
-  pub fn create_entity(&mut self, level: &mut Level, actionable_entity: Entity) {
-    match self.variant {
+  // The deletion tool removes every entity touching `actionable_entity`
+  pub fn create_entity(&mut self, level: &mut Level, camera: &Camera, actionable_entity: Entity) {
+    let command = match self.variant {
       LevelEntityVariant::Deletion => {
-        let delete_entities =
-          &Box::new(|current_entity: &Entity| !current_entity.is_inside_entity(current_entity));
-
-        level.background.retain(delete_entities);
-        level.indestructible.retain(delete_entities);
-        level.destructible.retain(delete_entities);
-        level.enemies.retain(delete_entities);
-        level.main_character.retain(delete_entities);
-        level.effects.retain(delete_entities);
-        level.foreground.retain(delete_entities);
+        let mut deleted_entities = vec![];
+        for layer in LAYERS.iter() {
+          if let Some(layer_entities) = layer.layer_mut(level) {
+            for (index, entity) in layer_entities.iter().enumerate() {
+              if entity.is_inside_entity(&actionable_entity) {
+                deleted_entities.push((*layer, index, entity.clone()));
+              }
+            }
+          }
+        }
+        if deleted_entities.is_empty() {
+          return;
+        }
+        // Indexes behind deleted entities shift, so the selection can't be trusted anymore
+        self.stop_inspecting(level);
+        self.selection.clear();
+        EditCommand::Delete { entities: deleted_entities }
+      }
+      LevelEntityVariant::Selection => return,
+      layer => {
+        let mut entity = actionable_entity;
+        // Clicking twice on the same spot places the picked sprite at its own size
+        let keeps_size = match self.sprite_picker.picked {
+          Some(sprite_sheet_rect) => {
+            entity.sprite_sheet_rect = Some(sprite_sheet_rect);
+            if entity.dimensions.0 < 2 && entity.dimensions.1 < 2 {
+              entity.dimensions = (sprite_sheet_rect.2, sprite_sheet_rect.3);
+              true
+            } else {
+              false
+            }
+          }
+          None => false,
+        };
+        let entity = self.snap_new_entity(level, layer, camera, entity, keeps_size);
+        EditCommand::Create { layer, entities: vec![entity] }
+      }
+    };
+    self.history.execute(level, command);
+  }
+  // Edges of the unselected entities in `layer`, which entities in that layer snap to
+  fn snap_targets(&self, level: &Level, layer: LevelEntityVariant, axis: GuideAxis) -> Vec<f32> {
+    let entities = layer
+      .layer(level)
+      .into_iter()
+      .flatten()
+      .enumerate()
+      .filter(|(index, _)| !self.selection.contains(&(layer, *index)))
+      .map(|(_, entity)| entity);
+    entity_edges(entities, axis)
+  }
+  // The offset that snaps one of `edges` of an entity in `layer`, with the guide it snapped to
+  fn snap_axis(
+    &self,
+    level: &Level,
+    layer: LevelEntityVariant,
+    parallax: (f32, f32),
+    axis: GuideAxis,
+    edges: &[f32],
+    camera: &Camera,
+  ) -> (f32, Option<Guide>) {
+    let scale = match axis {
+      GuideAxis::Vertical => camera.scale.0,
+      GuideAxis::Horizontal => camera.scale.1,
+    };
+    let targets = self.snap_targets(level, layer, axis);
+    let (offset, target) = self.snapping.snap_edges(edges, &targets, scale);
+    (offset, target.map(|position| Guide { axis, position, parallax }))
+  }
+  // Every edge of a new entity snaps on its own, it stays at least one grid cell large.
+  // With `keeps_size` the entity snaps like it's being moved instead.
+  fn snap_new_entity(
+    &self,
+    level: &Level,
+    layer: LevelEntityVariant,
+    camera: &Camera,
+    mut entity: Entity,
+    keeps_size: bool,
+  ) -> Entity {
+    let parallax = entity.parallax;
+    if keeps_size {
+      let (left, top) = entity.position;
+      let right = left + entity.dimensions.0 as f32;
+      let bottom = top + entity.dimensions.1 as f32;
+      let (offset_x, _) =
+        self.snap_axis(level, layer, parallax, GuideAxis::Vertical, &[left, right], camera);
+      let (offset_y, _) =
+        self.snap_axis(level, layer, parallax, GuideAxis::Horizontal, &[top, bottom], camera);
+      entity.position = (left + offset_x, top + offset_y);
+      return entity;
+    }
+    let snap =
+      |edge: f32, axis| edge + self.snap_axis(level, layer, parallax, axis, &[edge], camera).0;
+    let min_size = if self.snapping.is_enabled { self.snapping.grid_size as f32 } else { 1.0 };
+    let (left, top) = entity.position;
+    let left_edge = snap(left, GuideAxis::Vertical);
+    let top_edge = snap(top, GuideAxis::Horizontal);
+    let right_edge =
+      snap(left + entity.dimensions.0 as f32, GuideAxis::Vertical).max(left_edge + min_size);
+    let bottom_edge =
+      snap(top + entity.dimensions.1 as f32, GuideAxis::Horizontal).max(top_edge + min_size);
+    entity.position = (left_edge, top_edge);
+    entity.dimensions =
+      ((right_edge - left_edge).round() as u32, (bottom_edge - top_edge).round() as u32);
+    entity
+  }
+  pub fn undo(&mut self, level: &mut Level) {
+    self.inspector.reset();
+    self.selection.clear();
+    self.drag = None;
+    self.history.undo(level);
+  }
+  pub fn redo(&mut self, level: &mut Level) {
+    self.inspector.reset();
+    self.selection.clear();
+    self.drag = None;
+    self.history.redo(level);
+  }
+  // For changes the history can't follow, like simulating or reloading the level
+  pub fn reset_history(&mut self) {
+    self.inspector.reset();
+    self.selection.clear();
+    self.drag = None;
+    self.history.clear();
+  }
+  // The history addresses entities by their index in a layer, so it can't outlive the level
+  pub fn replace_level(&mut self, level: &mut Level, replacement: Level) {
+    *level = replacement;
+    self.reset_history();
+  }
+  pub fn selected_entities<'a>(&self, level: &'a Level) -> Vec<&'a Entity> {
+    self
+      .selection
+      .iter()
+      .filter_map(|(layer, index)| layer.layer(level).and_then(|entities| entities.get(*index)))
+      .collect()
+  }
+  // The inspector only shows a single selected entity
+  fn inspected_entity(&self) -> Option<EntityRef> {
+    match self.selection[..] {
+      [entity_ref] => Some(entity_ref),
+      _ => None,
+    }
+  }
+  fn stop_inspecting(&mut self, level: &mut Level) {
+    if let Some(entity_ref) = self.inspected_entity() {
+      self.inspector.finish(level, &mut self.history, entity_ref);
+    }
+  }
+  // While typing into the inspector, keys are text instead of actions
+  pub fn is_typing(&self) -> bool {
+    self.inspector.is_editing()
+  }
+  pub fn inspector_click(
+    &mut self,
+    level: &mut Level,
+    position: (i32, i32),
+    dimensions: (u16, u16),
+  ) -> bool {
+    match self.inspected_entity() {
+      Some(entity_ref) => {
+        self.inspector.click(level, &mut self.history, entity_ref, position, dimensions)
       }
-      LevelEntityVariant::Background => {
-        level.background.push(actionable_entity);
+      None => false,
+    }
+  }
+  pub fn inspector_event(&mut self, level: &mut Level, event: &Event) {
+    if let Some(entity_ref) = self.inspected_entity() {
+      self.inspector.handle_event(level, &mut self.history, entity_ref, event);
+    }
+  }
+  pub fn draw_inspector(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    level: &Level,
+    dimensions: (u16, u16),
+  ) {
+    if let [entity] = self.selected_entities(level)[..] {
+      self.inspector.draw(canvas, font, localization, entity, dimensions);
+    }
+  }
+  // The topmost entity under `position`, foreground first
+  fn entity_at(level: &Level, camera: &Camera, position: (i32, i32)) -> Option<EntityRef> {
+    LAYERS.iter().rev().find_map(|layer| {
+      let entities = layer.layer(level)?;
+      let index = entities
+        .iter()
+        .rposition(|entity| canvas_rect(entity, camera).contains_point(Point::from(position)))?;
+      Some((*layer, index))
+    })
+  }
+  fn selected_handle(
+    &self,
+    level: &Level,
+    camera: &Camera,
+    position: (i32, i32),
+  ) -> Option<ResizeHandle> {
+    match self.selected_entities(level)[..] {
+      [entity] => handle_rects(canvas_rect(entity, camera))
+        .iter()
+        .find(|(_, rect)| rect.contains_point(Point::from(position)))
+        .map(|(handle, _)| *handle),
+      _ => None,
+    }
+  }
+  // `is_adding` toggles entities in and out of the selection instead of replacing it
+  pub fn mouse_down(
+    &mut self,
+    level: &mut Level,
+    camera: &Camera,
+    position: (i32, i32),
+    is_adding: bool,
+  ) {
+    self.stop_inspecting(level);
+    if let Some(handle) = self.selected_handle(level, camera, position) {
+      let original = Box::new(self.selected_entities(level)[0].clone());
+      self.drag = Some(EditorDrag::Resize { handle, start: position, original });
+      return;
+    }
+    match EditorMenu::entity_at(level, camera, position) {
+      Some(entity_ref) if is_adding && self.selection.contains(&entity_ref) => {
+        self.selection.retain(|selected| *selected != entity_ref);
       }
-      LevelEntityVariant::Indestructible => {
-        level.indestructible.push(actionable_entity);
+      Some(entity_ref) => {
+        if !self.selection.contains(&entity_ref) {
+          if !is_adding {
+            self.selection.clear();
+          }
+          self.selection.push(entity_ref);
+        }
+        let originals = self.selected_entities(level).into_iter().cloned().collect();
+        self.drag = Some(EditorDrag::Move { start: position, originals });
       }
-      LevelEntityVariant::Destructible => {
-        level.destructible.push(actionable_entity);
+      None => {
+        if !is_adding {
+          self.selection.clear();
+        }
+        self.drag = Some(EditorDrag::BoxSelect { start: position, end: position });
+      }
+    }
+  }
+  pub fn mouse_motion(&mut self, level: &mut Level, camera: &Camera, position: (i32, i32)) {
+    if self.sprite_picker.is_dragging() {
+      self.sprite_picker.mouse_motion(camera.dimensions, position);
+      return;
+    }
+    if let Some(EditorDrag::BoxSelect { end, .. }) = &mut self.drag {
+      *end = position;
+      return;
+    }
+    let mut guides = vec![];
+    match &self.drag {
+      Some(EditorDrag::Move { start, originals }) => {
+        let mut delta = world_delta(*start, position, camera);
+        // The first selected entity snaps, the others keep their distance to it
+        if let (Some((layer, _)), Some(leader)) = (self.selection.first(), originals.first()) {
+          let left = leader.position.0 + delta.0;
+          let top = leader.position.1 + delta.1;
+          let (offset_x, guide_x) = self.snap_axis(
+            level,
+            *layer,
+            leader.parallax,
+            GuideAxis::Vertical,
+            &[left, left + leader.dimensions.0 as f32],
+            camera,
+          );
+          let (offset_y, guide_y) = self.snap_axis(
+            level,
+            *layer,
+            leader.parallax,
+            GuideAxis::Horizontal,
+            &[top, top + leader.dimensions.1 as f32],
+            camera,
+          );
+          delta = (delta.0 + offset_x, delta.1 + offset_y);
+          guides.extend(guide_x.into_iter().chain(guide_y));
+        }
+        for ((layer, index), original) in self.selection.iter().zip(originals.iter()) {
+          if let Some(entity) = layer.layer_mut(level).and_then(|entities| entities.get_mut(*index))
+          {
+            entity.position = (original.position.0 + delta.0, original.position.1 + delta.1);
+          }
+        }
+      }
+      Some(EditorDrag::Resize { handle, start, original }) => {
+        let delta = world_delta(*start, position, camera);
+        if let Some((layer, index)) = self.selection.first() {
+          // Only the edges the handle moves snap
+          let (is_left, is_top) = handle_sides(*handle);
+          let edge_x = original.position.0
+            + if is_left { 0.0 } else { original.dimensions.0 as f32 }
+            + delta.0;
+          let edge_y =
+            original.position.1 + if is_top { 0.0 } else { original.dimensions.1 as f32 } + delta.1;
+          let (offset_x, guide_x) = self.snap_axis(
+            level,
+            *layer,
+            original.parallax,
+            GuideAxis::Vertical,
+            &[edge_x],
+            camera,
+          );
+          let (offset_y, guide_y) = self.snap_axis(
+            level,
+            *layer,
+            original.parallax,
+            GuideAxis::Horizontal,
+            &[edge_y],
+            camera,
+          );
+          guides.extend(guide_x.into_iter().chain(guide_y));
+          if let Some(entity) = layer.layer_mut(level).and_then(|entities| entities.get_mut(*index))
+          {
+            *entity = resized(original, *handle, (delta.0 + offset_x, delta.1 + offset_y));
+          }
+        }
+      }
+      Some(EditorDrag::BoxSelect { .. }) | None => {}
+    }
+    self.guides = guides;
+  }
+  pub fn mouse_up(&mut self, level: &mut Level, camera: &Camera) {
+    if self.sprite_picker.is_dragging() {
+      self.sprite_picker.mouse_up();
+      return;
+    }
+    match self.drag.take() {
+      Some(EditorDrag::Move { originals, .. }) => self.commit_changes(level, originals),
+      Some(EditorDrag::Resize { original, .. }) => self.commit_changes(level, vec![*original]),
+      Some(EditorDrag::BoxSelect { start, end }) => {
+        let box_rect = box_rect(start, end);
+        for layer in LAYERS.iter() {
+          for (index, entity) in layer.layer(level).into_iter().flatten().enumerate() {
+            let is_inside = box_rect.has_intersection(canvas_rect(entity, camera));
+            if is_inside && !self.selection.contains(&(*layer, index)) {
+              self.selection.push((*layer, index));
+            }
+          }
+        }
       }
-      LevelEntityVariant::Enemies => {
-        level.enemies.push(actionable_entity);
+      None => {}
+    }
+  }
+  // Returns whether the sprite picker took the click
+  pub fn sprite_picker_click(
+    &mut self,
+    level: &mut Level,
+    position: (i32, i32),
+    dimensions: (u16, u16),
+  ) -> bool {
+    match self.sprite_picker.mouse_down(dimensions, position) {
+      PickerClick::Missed => false,
+      PickerClick::Handled => true,
+      PickerClick::AssignToSelection => {
+        self.stop_inspecting(level);
+        let originals = self.selected_entities(level).into_iter().cloned().collect::<Vec<_>>();
+        for (layer, index) in &self.selection {
+          if let Some(entity) = layer.layer_mut(level).and_then(|entities| entities.get_mut(*index))
+          {
+            entity.sprite_sheet_rect = self.sprite_picker.picked;
+          }
+        }
+        self.commit_changes(level, originals);
+        true
       }
-      LevelEntityVariant::MainCharacter => {
-        level.main_character.push(actionable_entity);
+    }
+  }
+  pub fn draw_sprite_picker(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    sprite_sheet: &Texture,
+    dimensions: (u16, u16),
+  ) {
+    if self.sprite_picker.is_open {
+      let selection_count = self.selection.len();
+      self.sprite_picker.draw(
+        canvas,
+        font,
+        localization,
+        sprite_sheet,
+        selection_count,
+        dimensions,
+      );
+    }
+  }
+  // Moves the selection one step towards `direction` as one undo step. While snapping, a step
+  // is a grid cell.
+  pub fn nudge(&mut self, level: &mut Level, direction: (f32, f32)) {
+    let step = if self.snapping.is_enabled { self.snapping.grid_size as f32 } else { 1.0 };
+    let delta = (direction.0 * step, direction.1 * step);
+    let originals = self.selected_entities(level).into_iter().cloned().collect::<Vec<_>>();
+    for ((layer, index), original) in self.selection.iter().zip(originals.iter()) {
+      if let Some(entity) = layer.layer_mut(level).and_then(|entities| entities.get_mut(*index)) {
+        entity.position = (original.position.0 + delta.0, original.position.1 + delta.1);
       }
-      LevelEntityVariant::Effects => {
-        level.effects.push(actionable_entity);
+    }
+    self.commit_changes(level, originals);
+  }
+  // Records the difference between `originals` and the selected entities as they are now
+  fn commit_changes(&mut self, level: &mut Level, originals: Vec<Entity>) {
+    let changes = self
+      .selection
+      .iter()
+      .zip(originals)
+      .filter_map(|((layer, index), before)| {
+        let after = layer.layer(level)?.get(*index)?.clone();
+        let is_changed = after.position != before.position
+          || after.dimensions != before.dimensions
+          || after.sprite_sheet_rect != before.sprite_sheet_rect;
+        if is_changed {
+          Some((*layer, *index, before, after))
+        } else {
+          None
+        }
+      })
+      .collect::<Vec<_>>();
+    if !changes.is_empty() {
+      self.history.execute(level, EditCommand::Change { entities: changes });
+    }
+  }
+  pub fn draw_selection(&self, canvas: &mut WindowCanvas, level: &Level, camera: &Camera) {
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(SELECTION_COLOR);
+    let selected_entities = self.selected_entities(level);
+    for entity in &selected_entities {
+      canvas.draw_rect(canvas_rect(entity, camera)).unwrap();
+    }
+    if let [entity] = selected_entities[..] {
+      for (_, handle_rect) in handle_rects(canvas_rect(entity, camera)).iter() {
+        canvas.fill_rect(*handle_rect).unwrap();
       }
-      LevelEntityVariant::Foreground => {
-        level.foreground.push(actionable_entity);
+    }
+    match self.drag {
+      Some(EditorDrag::BoxSelect { start, end }) => {
+        canvas.set_draw_color(BOX_SELECT_COLOR);
+        canvas.draw_rect(box_rect(start, end)).unwrap();
       }
+      Some(_) => draw_guides(canvas, camera, &self.guides),
+      None => {}
     }
+    canvas.set_draw_color(original_color);
   }
 }
+
+#[test]
+fn selection_moves_with_parallax_and_undoes_in_one_step() {
+  let mut level = Level {
+    name: None,
+    background: vec![Entity::new(0.0, 0.0, 20, 20)],
+    indestructible: vec![Entity::new(200.0, 0.0, 20, 20)],
+    destructible: vec![],
+    enemies: vec![],
+    main_character: vec![],
+    effects: vec![],
+    foreground: vec![],
+  };
+  level.background[0].parallax = (0.5, 0.5);
+  let mut camera = Camera::new((900, 600));
+  camera.position = (100.0, 0.0);
+  let mut editor_menu = EditorMenu::new(&EditorConfig {
+    history_depth: 10,
+    grid_size: 10,
+    guide_distance: 6.0,
+    sprite_frame_size: (32, 32),
+  });
+
+  // The background only scrolls half as far, so it sits at canvas x 400 instead of 350
+  editor_menu.mouse_down(&mut level, &camera, (405, 305), false);
+  assert_eq!(editor_menu.selection, vec![(LevelEntityVariant::Background, 0)]);
+  editor_menu.mouse_motion(&mut level, &camera, (415, 310));
+  editor_menu.mouse_motion(&mut level, &camera, (425, 315));
+  editor_menu.mouse_up(&mut level, &camera);
+  assert_eq!(level.background[0].position, (20.0, 10.0));
+
+  editor_menu.mouse_down(&mut level, &camera, (0, 0), false);
+  editor_menu.mouse_motion(&mut level, &camera, (899, 599));
+  editor_menu.mouse_up(&mut level, &camera);
+  assert_eq!(editor_menu.selection.len(), 2);
+
+  editor_menu.undo(&mut level);
+  assert_eq!(level.background[0].position, (0.0, 0.0));
+  assert!(editor_menu.selection.is_empty());
+}
+
+#[test]
+fn replacing_the_level_forgets_the_history() {
+  let mut level = Level {
+    name: None,
+    background: vec![],
+    indestructible: vec![Entity::new(0.0, 0.0, 20, 20), Entity::new(100.0, 0.0, 20, 20)],
+    destructible: vec![],
+    enemies: vec![],
+    main_character: vec![],
+    effects: vec![],
+    foreground: vec![],
+  };
+  let reloaded_level = level.clone();
+  let camera = Camera::new((900, 600));
+  let mut editor_menu = EditorMenu::new(&EditorConfig {
+    history_depth: 10,
+    grid_size: 10,
+    guide_distance: 6.0,
+    sprite_frame_size: (32, 32),
+  });
+
+  editor_menu.mouse_down(&mut level, &camera, (555, 305), false);
+  editor_menu.mouse_motion(&mut level, &camera, (575, 305));
+  editor_menu.mouse_up(&mut level, &camera);
+  assert_ne!(level.indestructible[1].position, (100.0, 0.0));
+
+  // Undoing the move after a reload or restart mustn't move the reloaded entity
+  editor_menu.replace_level(&mut level, reloaded_level);
+  editor_menu.undo(&mut level);
+  assert_eq!(level.indestructible[0].position, (0.0, 0.0));
+  assert_eq!(level.indestructible[1].position, (100.0, 0.0));
+}
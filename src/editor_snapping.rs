@@ -0,0 +1,151 @@
+use super::camera::Camera;
+use super::entity::Entity;
+use sdl2::pixels::Color;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+// Grid lines closer than this on the canvas are thinned out, so zooming out doesn't fill it
+static MIN_GRID_SPACING: f32 = 8.0;
+static GRID_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 40 };
+static GUIDE_COLOR: Color = Color { r: 120, g: 255, b: 120, a: 0xff };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuideAxis {
+  Vertical,
+  Horizontal,
+}
+
+// An edge of another entity that something snapped to, in world units of a layer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guide {
+  pub axis: GuideAxis,
+  pub position: f32,
+  pub parallax: (f32, f32),
+}
+
+// The left and right or top and bottom edges of `entities`
+pub fn entity_edges<'a>(entities: impl Iterator<Item = &'a Entity>, axis: GuideAxis) -> Vec<f32> {
+  entities
+    .flat_map(|entity| match axis {
+      GuideAxis::Vertical => {
+        vec![entity.position.0, entity.position.0 + entity.dimensions.0 as f32]
+      }
+      GuideAxis::Horizontal => {
+        vec![entity.position.1, entity.position.1 + entity.dimensions.1 as f32]
+      }
+    })
+    .collect()
+}
+
+pub struct Snapping {
+  pub is_enabled: bool,
+  pub grid_size: u32,
+  // How close edges have to get to stick together, in canvas pixels
+  guide_distance: f32,
+}
+
+impl Snapping {
+  pub fn new(grid_size: u32, guide_distance: f32) -> Self {
+    Self { is_enabled: true, grid_size: grid_size.max(1), guide_distance }
+  }
+  pub fn snap_to_grid(&self, value: f32) -> f32 {
+    if !self.is_enabled {
+      return value;
+    }
+    (value / self.grid_size as f32).round() * self.grid_size as f32
+  }
+  // The offset that puts one of `edges` onto the closest of `targets`, together with that target.
+  // Without a target in reach the first edge goes onto the grid instead. `scale` is the zoom.
+  pub fn snap_edges(&self, edges: &[f32], targets: &[f32], scale: f32) -> (f32, Option<f32>) {
+    if !self.is_enabled || edges.is_empty() {
+      return (0.0, None);
+    }
+    let max_distance = self.guide_distance / scale;
+    let closest = edges
+      .iter()
+      .flat_map(|edge| targets.iter().map(move |target| (target - edge, *target)))
+      .filter(|(offset, _)| offset.abs() <= max_distance)
+      .min_by(|(offset, _), (other_offset, _)| {
+        offset.abs().partial_cmp(&other_offset.abs()).unwrap()
+      });
+    match closest {
+      Some((offset, target)) => (offset, Some(target)),
+      None => (self.snap_to_grid(edges[0]) - edges[0], None),
+    }
+  }
+  // Grid lines of the layers without parallax, every second one is dropped until they're far
+  // enough apart
+  pub fn draw_grid(&self, canvas: &mut WindowCanvas, camera: &Camera) {
+    if !self.is_enabled {
+      return;
+    }
+    let offset = ((camera.dimensions.0 / 2) as u32, (camera.dimensions.1 / 2) as u32);
+    let top_left = Entity::canvas_to_world_position((0.0, 0.0), (1.0, 1.0), camera, offset);
+    let bottom_right = Entity::canvas_to_world_position(
+      (camera.dimensions.0 as f32, camera.dimensions.1 as f32),
+      (1.0, 1.0),
+      camera,
+      offset,
+    );
+    let mut spacing = self.grid_size as f32;
+    while spacing * camera.scale.0.min(camera.scale.1) < MIN_GRID_SPACING {
+      spacing *= 2.0;
+    }
+
+    let original_color = canvas.draw_color();
+    let original_blend_mode = canvas.blend_mode();
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(GRID_COLOR);
+    let mut x = (top_left.0 / spacing).ceil() * spacing;
+    while x <= bottom_right.0 {
+      let canvas_x = ((x - camera.position.0) * camera.scale.0) as i32 + offset.0 as i32;
+      canvas.draw_line((canvas_x, 0), (canvas_x, camera.dimensions.1 as i32)).unwrap();
+      x += spacing;
+    }
+    let mut y = (top_left.1 / spacing).ceil() * spacing;
+    while y <= bottom_right.1 {
+      let canvas_y = ((y - camera.position.1) * camera.scale.1) as i32 + offset.1 as i32;
+      canvas.draw_line((0, canvas_y), (camera.dimensions.0 as i32, canvas_y)).unwrap();
+      y += spacing;
+    }
+    canvas.set_blend_mode(original_blend_mode);
+    canvas.set_draw_color(original_color);
+  }
+}
+
+pub fn draw_guides(canvas: &mut WindowCanvas, camera: &Camera, guides: &[Guide]) {
+  let original_color = canvas.draw_color();
+  canvas.set_draw_color(GUIDE_COLOR);
+  let offset = ((camera.dimensions.0 / 2) as i32, (camera.dimensions.1 / 2) as i32);
+  for guide in guides {
+    match guide.axis {
+      GuideAxis::Vertical => {
+        let x = ((guide.position - camera.position.0 * guide.parallax.0) * camera.scale.0) as i32
+          + offset.0;
+        canvas.draw_line((x, 0), (x, camera.dimensions.1 as i32)).unwrap();
+      }
+      GuideAxis::Horizontal => {
+        let y = ((guide.position - camera.position.1 * guide.parallax.1) * camera.scale.1) as i32
+          + offset.1;
+        canvas.draw_line((0, y), (camera.dimensions.0 as i32, y)).unwrap();
+      }
+    }
+  }
+  canvas.set_draw_color(original_color);
+}
+
+#[test]
+fn snapping_prefers_nearby_edges_over_the_grid() {
+  let mut snapping = Snapping::new(16, 6.0);
+  let platform = Entity::new(100.0, 40.0, 50, 10);
+  let targets = entity_edges(vec![&platform].into_iter(), GuideAxis::Vertical);
+  assert_eq!(targets, vec![100.0, 150.0]);
+
+  // The right edge at 147 is 3 pixels from the platform's right edge
+  assert_eq!(snapping.snap_edges(&[127.0, 147.0], &targets, 1.0), (3.0, Some(150.0)));
+  // Zoomed in twice, 6 canvas pixels only reach 3 world units
+  assert_eq!(snapping.snap_edges(&[20.0, 146.0], &targets, 2.0), (-4.0, None));
+  assert_eq!(snapping.snap_to_grid(25.0), 32.0);
+
+  snapping.is_enabled = false;
+  assert_eq!(snapping.snap_edges(&[127.0, 147.0], &targets, 1.0), (0.0, None));
+}
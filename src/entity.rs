@@ -1,24 +1,43 @@
-use super::camera::Camera;
+use super::camera::{Camera, CameraEffect};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
   Kill,
   Teleport(f32, f32),
+  CameraEffect(CameraEffect),
+  Dialog(String),
+  Checkpoint,
+  LevelComplete,
+  Collect {
+    item: String,
+    score: u32,
+  },
+  // Raised by collisions with something that deals damage, level files can't use it
+  #[serde(skip)]
+  Damage,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Event {
   pub event_type: EventType,
   #[serde(default = "default_receiving_entity_ids")]
   pub receiving_entity_ids: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionSide {
+  Top,
+  Bottom,
+  Left,
+  Right,
+}
+
 fn default_receiving_entity_ids() -> Vec<String> {
   vec![]
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entity {
   pub sprite_sheet_rect: Option<(i32, i32, u32, u32)>,
   pub aim_direction: Option<f32>,
@@ -31,6 +50,7 @@ pub struct Entity {
   pub id: Option<String>,
   pub event: Option<Event>,
   pub health: Option<i32>,
+  pub ammo: Option<u32>,
   pub damage_factor: Option<f32>,
   #[serde(default = "default_step_height")]
   pub step_height: f32,
@@ -40,6 +60,10 @@ pub struct Entity {
   pub acceleration: (f32, f32),
   #[serde(default = "default_parallax")]
   pub parallax: (f32, f32),
+  #[serde(skip)]
+  pub triggered_events: Vec<EventType>,
+  #[serde(skip)]
+  pub collision_sides: Vec<CollisionSide>,
 }
 
 fn default_bounciness() -> f32 {
@@ -70,6 +94,7 @@ impl Entity {
       aim_direction: None,
       event: None,
       health: None,
+      ammo: None,
       damage_factor: None,
       bounciness: default_bounciness(),
       slippiness: default_slippiness(),
@@ -78,6 +103,8 @@ impl Entity {
       velocity: default_velocity(),
       acceleration: (0.0, 1.0),
       parallax: default_parallax(),
+      triggered_events: vec![],
+      collision_sides: vec![],
     }
   }
   pub fn parallax_x(mut self, parallax_x: f32) -> Self {
@@ -112,7 +139,7 @@ impl Entity {
     self.damage_factor = damage_factor;
     self
   }
-  fn is_triggering(&self, entity: &Entity) -> bool {
+  pub fn is_triggering(&self, entity: &Entity) -> bool {
     if let Some(event) = &entity.event {
       event.receiving_entity_ids.len() == 0
         || match &self.id {
@@ -132,6 +159,23 @@ impl Entity {
       EventType::Kill => {
         self.id = Some("dying".to_string());
       }
+      EventType::CameraEffect(_)
+      | EventType::Dialog(_)
+      | EventType::Checkpoint
+      | EventType::LevelComplete
+      | EventType::Collect { .. }
+      | EventType::Damage => {
+        self.triggered_events.push(event.event_type.clone());
+      }
+    }
+  }
+  // Whatever deals damage is used up on something with health, which reports that it was hit
+  fn hit(&mut self, entity: &Entity) {
+    if entity.health.is_some() && self.damage_factor.is_some() {
+      self.id = Some("dying".to_string());
+    }
+    if self.health.is_some() && entity.damage_factor.is_some() {
+      self.triggered_events.push(EventType::Damage);
     }
   }
   pub fn is_touching_ground(&self, interactive_entities: &Vec<Self>) -> bool {
@@ -140,7 +184,7 @@ impl Entity {
       None => false,
     }
   }
-  fn find_ground_entity<'a>(&self, interactive_entities: &'a Vec<Self>) -> Option<&'a Entity> {
+  pub fn find_ground_entity<'a>(&self, interactive_entities: &'a Vec<Self>) -> Option<&'a Entity> {
     let lower_end = self.position.1 as i32 + self.dimensions.1 as i32;
     interactive_entities.iter().find(|entity| {
       entity.position.1 as i32 == lower_end
@@ -162,14 +206,28 @@ impl Entity {
     )
   }
 
+  // Inverse of `to_canvas_coordinates` for a single point on a layer with the given parallax
+  pub fn canvas_to_world_position(
+    (x, y): (f32, f32),
+    (parallax_x, parallax_y): (f32, f32),
+    camera: &Camera,
+    offset: (u32, u32),
+  ) -> (f32, f32) {
+    (
+      (x + camera.position.0 * parallax_x * camera.scale.0 - offset.0 as f32) / camera.scale.0,
+      (y + camera.position.1 * parallax_y * camera.scale.1 - offset.1 as f32) / camera.scale.1,
+    )
+  }
   pub fn from_canvas_coordinates(
     (x, y, width, height, parallax_x, parallax_y): (f32, f32, u32, u32, f32, f32),
     camera: &Camera,
     offset: (u32, u32),
   ) -> Self {
+    let (position_x, position_y) =
+      Entity::canvas_to_world_position((x, y), (parallax_x, parallax_y), camera, offset);
     Entity::new(
-      (x + camera.position.0 * parallax_x * camera.scale.0 - offset.0 as f32) / camera.scale.0,
-      (y + camera.position.1 * parallax_y * camera.scale.1 - offset.1 as f32) / camera.scale.1,
+      position_x,
+      position_y,
       (width as f32 / camera.scale.0) as u32,
       (height as f32 / camera.scale.1) as u32,
     )
@@ -177,6 +235,8 @@ impl Entity {
     .parallax_y(parallax_y)
   }
   pub fn next_state(&mut self, interactive_entities: &Vec<Self>) {
+    self.collision_sides.clear();
+
     if let Some(ground_entity) = self.find_ground_entity(&interactive_entities) {
       self.velocity.0 *= self.slippiness.max(ground_entity.slippiness);
     }
@@ -204,13 +264,10 @@ impl Entity {
         self.run_event(bottom_to_self.event.as_ref().unwrap());
       } else {
         self.position.1 = bottom_to_self.position.1 - self.dimensions.1 as f32;
+        self.collision_sides.push(CollisionSide::Bottom);
         self.velocity.1 *= -1.0 * self.bounciness * bottom_to_self.bounciness;
 
-        if let Some(health) = bottom_to_self.health {
-          if let Some(damage_factor) = self.damage_factor {
-            self.id = Some("dying".to_string());
-          }
-        }
+        self.hit(bottom_to_self);
       }
       is_on_ground = true;
     } else if let Some(top_to_self) = collided_entities
@@ -221,12 +278,9 @@ impl Entity {
         self.run_event(top_to_self.event.as_ref().unwrap());
       } else {
         self.position.1 = top_to_self.position.1 + top_to_self.dimensions.1 as f32;
+        self.collision_sides.push(CollisionSide::Top);
         self.velocity.1 *= -1.0 * self.bounciness * top_to_self.bounciness;
-        if let Some(health) = top_to_self.health {
-          if let Some(damage_factor) = self.damage_factor {
-            self.id = Some("dying".to_string());
-          }
-        }
+        self.hit(top_to_self);
       }
     }
     if let Some(right_to_self) = collided_entities
@@ -243,12 +297,9 @@ impl Entity {
           self.position.1 = right_to_self.position.1 - self.dimensions.1 as f32;
         } else {
           self.position.0 = right_to_self.position.0 - self.dimensions.0 as f32;
+          self.collision_sides.push(CollisionSide::Right);
           self.velocity.0 *= -1.0 * self.bounciness * right_to_self.bounciness;
-          if let Some(health) = right_to_self.health {
-            if let Some(damage_factor) = self.damage_factor {
-              self.id = Some("dying".to_string());
-            }
-          }
+          self.hit(right_to_self);
         }
       }
     } else if let Some(left_to_self) = collided_entities
@@ -265,12 +316,9 @@ impl Entity {
           self.position.1 = left_to_self.position.1 - self.dimensions.1 as f32;
         } else {
           self.position.0 = left_to_self.position.0 + left_to_self.dimensions.0 as f32;
+          self.collision_sides.push(CollisionSide::Left);
           self.velocity.0 *= -1.0 * self.bounciness * left_to_self.bounciness;
-          if let Some(health) = left_to_self.health {
-            if let Some(damage_factor) = self.damage_factor {
-              self.id = Some("dying".to_string());
-            }
-          }
+          self.hit(left_to_self);
         }
       }
     }
@@ -367,3 +415,35 @@ fn window_entity_coordinates_vs_actual_coordinates_plus_scale() {
   assert_eq!(entity.dimensions.0, entity_like_at_the_beginning.dimensions.0);
   assert_eq!(entity.dimensions.1, entity_like_at_the_beginning.dimensions.1);
 }
+
+#[test]
+fn canvas_to_world_position_inverts_parallax_and_zoom() {
+  let mut camera = Camera::new((900, 600));
+  camera.zoom(2.0);
+  camera.position = (300.0, -120.0);
+
+  let entity = Entity::new(75.0, 40.0, 10, 10).parallax_x(0.5).parallax_y(0.25);
+  let (x, y, _, _) = entity.to_canvas_coordinates(&camera, (450, 300));
+  let position = Entity::canvas_to_world_position((x, y), entity.parallax, &camera, (450, 300));
+
+  assert_eq!(position, entity.position);
+}
+
+#[test]
+fn entities_with_health_report_damage_without_losing_health() {
+  let mut hero = Entity::new(0.0, 0.0, 10, 10).velocity_y(5.0);
+  hero.health = Some(5);
+  let spikes = Entity::new(-10.0, 12.0, 30, 10).damage_factor(Some(2.0));
+
+  hero.next_state(&vec![spikes.clone()]);
+  assert_eq!(hero.health, Some(5));
+  assert_eq!(hero.id, None);
+  assert!(matches!(hero.triggered_events[..], [EventType::Damage]));
+
+  let mut bullet = Entity::new(0.0, 0.0, 2, 2).velocity_y(5.0).damage_factor(Some(1.0));
+  let mut enemy = Entity::new(-10.0, 4.0, 30, 10);
+  enemy.health = Some(3);
+  bullet.next_state(&vec![enemy, spikes]);
+  assert_eq!(bullet.id, Some("dying".to_string()));
+  assert!(bullet.triggered_events.is_empty());
+}
@@ -0,0 +1,570 @@
+use super::camera::CameraEffect;
+use super::dialog::DialogScript;
+use super::editor_history::{EditCommand, EditorHistory};
+use super::editor_menu::EntityRef;
+use super::entity::{Entity, Event, EventType};
+use super::level::Level;
+use super::localization::Localization;
+use super::text::{measure_text_line, show_text_line, Font};
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+use std::str::FromStr;
+
+static LETTER_SCALE: u8 = 1;
+static LETTER_GAP: f32 = 1.1;
+static ROW_HEIGHT: i32 = 12;
+static PANEL_WIDTH: u32 = 400;
+static PANEL_MARGIN: i32 = 10;
+static PANEL_PADDING: i32 = 6;
+static VALUE_COLUMN: i32 = 170;
+
+static PANEL_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static PANEL_BORDER_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static TITLE_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static LABEL_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+static VALUE_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static ERROR_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InspectorField {
+  Id,
+  Bounciness,
+  Slippiness,
+  StepHeight,
+  Health,
+  DamageFactor,
+  Parallax,
+  SpriteSheetRect,
+  Velocity,
+  Acceleration,
+  Event,
+  ReceivingEntityIds,
+}
+
+static FIELDS: [InspectorField; 12] = [
+  InspectorField::Id,
+  InspectorField::Bounciness,
+  InspectorField::Slippiness,
+  InspectorField::StepHeight,
+  InspectorField::Health,
+  InspectorField::DamageFactor,
+  InspectorField::Parallax,
+  InspectorField::SpriteSheetRect,
+  InspectorField::Velocity,
+  InspectorField::Acceleration,
+  InspectorField::Event,
+  InspectorField::ReceivingEntityIds,
+];
+
+fn parse_number<T: FromStr>(text: &str) -> Result<T, &'static str> {
+  text.trim().parse().map_err(|_| "inspector.error.number")
+}
+
+// Rust also parses "inf" and "NaN", neither survives the physics
+fn parse_float(text: &str) -> Result<f32, &'static str> {
+  parse_number(text).and_then(|number: f32| {
+    if number.is_finite() {
+      Ok(number)
+    } else {
+      Err("inspector.error.number")
+    }
+  })
+}
+
+// Splits "a, b, c" into exactly `count` trimmed values
+fn split_values<'a>(
+  text: &'a str,
+  count: usize,
+  error: &'static str,
+) -> Result<Vec<&'a str>, &'static str> {
+  let values = text.split(',').map(str::trim).collect::<Vec<_>>();
+  if values.len() == count && values.iter().all(|value| !value.is_empty()) {
+    Ok(values)
+  } else {
+    Err(error)
+  }
+}
+
+fn parse_pair(text: &str) -> Result<(f32, f32), &'static str> {
+  let values = split_values(text, 2, "inspector.error.pair")?;
+  Ok((parse_float(values[0])?, parse_float(values[1])?))
+}
+
+fn parse_optional<T>(
+  text: &str,
+  parse: impl Fn(&str) -> Result<T, &'static str>,
+) -> Result<Option<T>, &'static str> {
+  if text.trim().is_empty() {
+    Ok(None)
+  } else {
+    parse(text).map(Some)
+  }
+}
+
+// Events are written as their variant followed by comma separated arguments, like the JSON
+// variant names. Camera effects leave out the `CameraEffect` wrapper.
+fn event_type_text(event_type: &EventType) -> String {
+  match event_type {
+    EventType::Kill => "Kill".to_string(),
+    EventType::Teleport(x, y) => format!("Teleport {}, {}", x, y),
+    EventType::CameraEffect(CameraEffect::Shake(trauma)) => format!("Shake {}", trauma),
+    EventType::CameraEffect(CameraEffect::ZoomPunch(amount, duration)) => {
+      format!("ZoomPunch {}, {}", amount, duration)
+    }
+    EventType::CameraEffect(CameraEffect::PanTo(x, y, duration)) => {
+      format!("PanTo {}, {}, {}", x, y, duration)
+    }
+    EventType::CameraEffect(CameraEffect::FadeTo((r, g, b, a), duration)) => {
+      format!("FadeTo {}, {}, {}, {}, {}", r, g, b, a, duration)
+    }
+    EventType::Dialog(script) => format!("Dialog {}", script),
+    EventType::Checkpoint => "Checkpoint".to_string(),
+    EventType::LevelComplete => "LevelComplete".to_string(),
+    EventType::Collect { item, score } => format!("Collect {}, {}", item, score),
+    EventType::Damage => "Damage".to_string(),
+  }
+}
+
+fn parse_event_type(text: &str) -> Result<EventType, &'static str> {
+  let text = text.trim();
+  let (name, arguments) = match text.find(' ') {
+    Some(index) => (&text[..index], text[index..].trim()),
+    None => (text, ""),
+  };
+  let error = "inspector.error.event";
+  let values = |count| split_values(arguments, count, error);
+  let number = |value: &str| parse_float(value).map_err(|_| error);
+  let duration = |value: &str| parse_number::<u32>(value).map_err(|_| error);
+  let color = |value: &str| parse_number::<u8>(value).map_err(|_| error);
+  let event_type = match name {
+    "Kill" if arguments.is_empty() => EventType::Kill,
+    "Checkpoint" if arguments.is_empty() => EventType::Checkpoint,
+    "LevelComplete" if arguments.is_empty() => EventType::LevelComplete,
+    "Teleport" => {
+      let values = values(2)?;
+      EventType::Teleport(number(values[0])?, number(values[1])?)
+    }
+    "Shake" => EventType::CameraEffect(CameraEffect::Shake(number(values(1)?[0])?)),
+    "ZoomPunch" => {
+      let values = values(2)?;
+      EventType::CameraEffect(CameraEffect::ZoomPunch(number(values[0])?, duration(values[1])?))
+    }
+    "PanTo" => {
+      let values = values(3)?;
+      EventType::CameraEffect(CameraEffect::PanTo(
+        number(values[0])?,
+        number(values[1])?,
+        duration(values[2])?,
+      ))
+    }
+    "FadeTo" => {
+      let values = values(5)?;
+      EventType::CameraEffect(CameraEffect::FadeTo(
+        (color(values[0])?, color(values[1])?, color(values[2])?, color(values[3])?),
+        duration(values[4])?,
+      ))
+    }
+    "Dialog" if !arguments.is_empty() => {
+      DialogScript::read(arguments).map_err(|_| "inspector.error.dialog")?;
+      EventType::Dialog(arguments.to_string())
+    }
+    "Collect" => {
+      let values = values(2)?;
+      EventType::Collect { item: values[0].to_string(), score: duration(values[1])? }
+    }
+    _ => return Err(error),
+  };
+  Ok(event_type)
+}
+
+impl InspectorField {
+  // Labels are the level JSON keys, so the panel and the files read the same
+  fn label(self) -> &'static str {
+    match self {
+      InspectorField::Id => "id",
+      InspectorField::Bounciness => "bounciness",
+      InspectorField::Slippiness => "slippiness",
+      InspectorField::StepHeight => "step_height",
+      InspectorField::Health => "health",
+      InspectorField::DamageFactor => "damage_factor",
+      InspectorField::Parallax => "parallax",
+      InspectorField::SpriteSheetRect => "sprite_sheet_rect",
+      InspectorField::Velocity => "velocity",
+      InspectorField::Acceleration => "acceleration",
+      InspectorField::Event => "event",
+      InspectorField::ReceivingEntityIds => "receiving_entity_ids",
+    }
+  }
+  // Unset optional fields are empty
+  pub fn text(self, entity: &Entity) -> String {
+    let pair = |(x, y): (f32, f32)| format!("{}, {}", x, y);
+    match self {
+      InspectorField::Id => entity.id.clone().unwrap_or_default(),
+      InspectorField::Bounciness => entity.bounciness.to_string(),
+      InspectorField::Slippiness => entity.slippiness.to_string(),
+      InspectorField::StepHeight => entity.step_height.to_string(),
+      InspectorField::Health => entity.health.map(|health| health.to_string()).unwrap_or_default(),
+      InspectorField::DamageFactor => {
+        entity.damage_factor.map(|damage_factor| damage_factor.to_string()).unwrap_or_default()
+      }
+      InspectorField::Parallax => pair(entity.parallax),
+      InspectorField::SpriteSheetRect => entity
+        .sprite_sheet_rect
+        .map(|(x, y, width, height)| format!("{}, {}, {}, {}", x, y, width, height))
+        .unwrap_or_default(),
+      InspectorField::Velocity => pair(entity.velocity),
+      InspectorField::Acceleration => pair(entity.acceleration),
+      InspectorField::Event => {
+        entity.event.as_ref().map(|event| event_type_text(&event.event_type)).unwrap_or_default()
+      }
+      InspectorField::ReceivingEntityIds => {
+        entity.event.as_ref().map(|event| event.receiving_entity_ids.join(", ")).unwrap_or_default()
+      }
+    }
+  }
+  // Sets the field from `text`, errors are string ids and leave `entity` unchanged
+  pub fn apply(self, entity: &mut Entity, text: &str) -> Result<(), &'static str> {
+    let unit_interval = |value: f32| {
+      if (0.0..=1.0).contains(&value) {
+        Ok(value)
+      } else {
+        Err("inspector.error.range")
+      }
+    };
+    let non_negative =
+      |value: f32| if value >= 0.0 { Ok(value) } else { Err("inspector.error.negative") };
+    match self {
+      // Receivers are separated by commas, so ids can't contain any
+      InspectorField::Id if text.contains(',') => return Err("inspector.error.id"),
+      InspectorField::Id => {
+        entity.id = Some(text.trim().to_string()).filter(|id| !id.is_empty());
+      }
+      // Bounciness multiplies on collisions, bullets bounce off with more than 1
+      InspectorField::Bounciness => entity.bounciness = non_negative(parse_float(text)?)?,
+      InspectorField::Slippiness => entity.slippiness = unit_interval(parse_float(text)?)?,
+      InspectorField::StepHeight => entity.step_height = non_negative(parse_float(text)?)?,
+      InspectorField::Health => entity.health = parse_optional(text, parse_number)?,
+      InspectorField::DamageFactor => {
+        entity.damage_factor =
+          parse_optional(text, |text| parse_float(text).and_then(non_negative))?;
+      }
+      InspectorField::Parallax => entity.parallax = parse_pair(text)?,
+      InspectorField::SpriteSheetRect => {
+        entity.sprite_sheet_rect = parse_optional(text, |text| {
+          let values = split_values(text, 4, "inspector.error.rect")?;
+          let size = |value| {
+            parse_number::<u32>(value).ok().filter(|size| *size > 0).ok_or("inspector.error.rect")
+          };
+          Ok((
+            parse_number(values[0])?,
+            parse_number(values[1])?,
+            size(values[2])?,
+            size(values[3])?,
+          ))
+        })?;
+      }
+      InspectorField::Velocity => entity.velocity = parse_pair(text)?,
+      InspectorField::Acceleration => entity.acceleration = parse_pair(text)?,
+      InspectorField::Event => {
+        entity.event = match parse_optional(text, parse_event_type)? {
+          Some(event_type) => {
+            let receiving_entity_ids =
+              entity.event.take().map(|event| event.receiving_entity_ids).unwrap_or_default();
+            Some(Event { event_type, receiving_entity_ids })
+          }
+          None => None,
+        };
+      }
+      InspectorField::ReceivingEntityIds => {
+        let receiving_entity_ids = text
+          .split(',')
+          .map(str::trim)
+          .filter(|id| !id.is_empty())
+          .map(String::from)
+          .collect::<Vec<_>>();
+        match &mut entity.event {
+          Some(event) => event.receiving_entity_ids = receiving_entity_ids,
+          None if receiving_entity_ids.is_empty() => {}
+          None => return Err("inspector.error.receivers"),
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+// The field being typed into, the level shows the text as soon as it's valid
+struct FieldEdit {
+  field: usize,
+  text: String,
+  original: Entity,
+  error: Option<&'static str>,
+}
+
+pub struct EntityInspector {
+  edit: Option<FieldEdit>,
+}
+
+fn entity_mut(level: &mut Level, (layer, index): EntityRef) -> Option<&mut Entity> {
+  layer.layer_mut(level).and_then(|entities| entities.get_mut(index))
+}
+
+impl EntityInspector {
+  pub fn new() -> Self {
+    Self { edit: None }
+  }
+  pub fn is_editing(&self) -> bool {
+    self.edit.is_some()
+  }
+  fn panel_rect(dimensions: (u16, u16)) -> Rect {
+    let height = (FIELDS.len() as i32 + 4) * ROW_HEIGHT + PANEL_PADDING * 2;
+    Rect::new(
+      dimensions.0 as i32 - PANEL_WIDTH as i32 - PANEL_MARGIN,
+      30,
+      PANEL_WIDTH,
+      height as u32,
+    )
+  }
+  fn row_y(dimensions: (u16, u16), row: usize) -> i32 {
+    EntityInspector::panel_rect(dimensions).y() + PANEL_PADDING + (row as i32 + 1) * ROW_HEIGHT
+  }
+  fn begin(&mut self, level: &mut Level, entity_ref: EntityRef, field: usize) {
+    if let Some(entity) = entity_mut(level, entity_ref) {
+      self.edit = Some(FieldEdit {
+        field,
+        text: FIELDS[field].text(entity),
+        original: entity.clone(),
+        error: None,
+      });
+    }
+  }
+  fn preview(&mut self, level: &mut Level, entity_ref: EntityRef) {
+    if let (Some(edit), Some(entity)) = (&mut self.edit, entity_mut(level, entity_ref)) {
+      let mut previewed = edit.original.clone();
+      edit.error = FIELDS[edit.field].apply(&mut previewed, &edit.text).err();
+      *entity = if edit.error.is_none() { previewed } else { edit.original.clone() };
+    }
+  }
+  // Keeps editing while the text is invalid, so the error stays visible
+  fn commit(&mut self, level: &mut Level, history: &mut EditorHistory, entity_ref: EntityRef) {
+    self.preview(level, entity_ref);
+    let edit = match self.edit.take() {
+      Some(edit) if edit.error.is_none() => edit,
+      edit => {
+        self.edit = edit;
+        return;
+      }
+    };
+    // The preview replaces the whole entity, so the whole entity decides if anything changed
+    if let Some(entity) = entity_mut(level, entity_ref) {
+      if *entity != edit.original {
+        let (layer, index) = entity_ref;
+        let after = entity.clone();
+        history.execute(
+          level,
+          EditCommand::Change { entities: vec![(layer, index, edit.original, after)] },
+        );
+      }
+    }
+  }
+  pub fn cancel(&mut self, level: &mut Level, entity_ref: EntityRef) {
+    if let (Some(edit), Some(entity)) = (self.edit.take(), entity_mut(level, entity_ref)) {
+      *entity = edit.original;
+    }
+  }
+  // Valid text is applied, invalid text is dropped. For when the inspected entity goes away.
+  pub fn finish(&mut self, level: &mut Level, history: &mut EditorHistory, entity_ref: EntityRef) {
+    self.commit(level, history, entity_ref);
+    self.cancel(level, entity_ref);
+  }
+  // Forgets the edit without touching the level
+  pub fn reset(&mut self) {
+    self.edit = None;
+  }
+  // Returns whether the click hit the panel, clicking a row starts editing it
+  pub fn click(
+    &mut self,
+    level: &mut Level,
+    history: &mut EditorHistory,
+    entity_ref: EntityRef,
+    position: (i32, i32),
+    dimensions: (u16, u16),
+  ) -> bool {
+    if !EntityInspector::panel_rect(dimensions).contains_point(Point::from(position)) {
+      return false;
+    }
+    let clicked_field = (0..FIELDS.len()).find(|row| {
+      let y = EntityInspector::row_y(dimensions, *row);
+      position.1 >= y && position.1 < y + ROW_HEIGHT
+    });
+    if let Some(field) = clicked_field {
+      self.finish(level, history, entity_ref);
+      self.begin(level, entity_ref, field);
+    }
+    true
+  }
+  pub fn handle_event(
+    &mut self,
+    level: &mut Level,
+    history: &mut EditorHistory,
+    entity_ref: EntityRef,
+    event: &SdlEvent,
+  ) {
+    let field = match &mut self.edit {
+      Some(edit) => edit.field,
+      None => return,
+    };
+    match event {
+      SdlEvent::TextInput { text, .. } => {
+        if let Some(edit) = &mut self.edit {
+          edit.text.push_str(text);
+        }
+        self.preview(level, entity_ref);
+      }
+      SdlEvent::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+        if let Some(edit) = &mut self.edit {
+          edit.text.pop();
+        }
+        self.preview(level, entity_ref);
+      }
+      SdlEvent::KeyDown { keycode: Some(Keycode::Return), .. } => {
+        self.commit(level, history, entity_ref);
+      }
+      SdlEvent::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+        self.commit(level, history, entity_ref);
+        if !self.is_editing() {
+          self.begin(level, entity_ref, (field + 1) % FIELDS.len());
+        }
+      }
+      SdlEvent::KeyDown { keycode: Some(Keycode::Escape), .. } => self.cancel(level, entity_ref),
+      _ => {}
+    }
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    entity: &Entity,
+    dimensions: (u16, u16),
+  ) {
+    let panel_rect = EntityInspector::panel_rect(dimensions);
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(PANEL_COLOR);
+    canvas.fill_rect(panel_rect).unwrap();
+    canvas.set_draw_color(PANEL_BORDER_COLOR);
+    canvas.draw_rect(panel_rect).unwrap();
+    canvas.set_draw_color(original_color);
+
+    let left = panel_rect.x() + PANEL_PADDING;
+    let value_width = PANEL_WIDTH as i32 - VALUE_COLUMN - PANEL_PADDING * 2;
+    let mut show = |font: &mut Font, text: &str, position: (i32, i32), color: Color| {
+      show_text_line(canvas, font, text, position, LETTER_SCALE, LETTER_GAP, color);
+    };
+    show(
+      font,
+      localization.get("inspector.title"),
+      (left, panel_rect.y() + PANEL_PADDING),
+      TITLE_COLOR,
+    );
+    for (row, field) in FIELDS.iter().enumerate() {
+      let y = EntityInspector::row_y(dimensions, row);
+      show(font, field.label(), (left, y), LABEL_COLOR);
+      let (value, color) = match &self.edit {
+        Some(edit) if edit.field == row => {
+          let color = if edit.error.is_some() { ERROR_COLOR } else { VALUE_COLOR };
+          (format!("{}_", edit.text), color)
+        }
+        _ => (field.text(entity), LABEL_COLOR),
+      };
+      // Long values show their end, where the cursor is
+      let mut visible_value = value.as_str();
+      while measure_text_line(font, visible_value, LETTER_SCALE, LETTER_GAP).0 as i32 > value_width
+      {
+        let mut characters = visible_value.chars();
+        characters.next();
+        visible_value = characters.as_str();
+      }
+      show(font, visible_value, (left + VALUE_COLUMN, y), color);
+    }
+
+    let footer_y = EntityInspector::row_y(dimensions, FIELDS.len() + 1);
+    let (footer, color) = match &self.edit {
+      Some(FieldEdit { error: Some(error), .. }) => (localization.get(error), ERROR_COLOR),
+      _ => (localization.get("inspector.hint"), LABEL_COLOR),
+    };
+    show(font, footer, (left, footer_y), color);
+  }
+}
+
+#[test]
+fn inspector_fields_validate_and_round_trip() {
+  let mut entity = Entity::new(0.0, 0.0, 10, 10);
+
+  assert_eq!(InspectorField::Bounciness.apply(&mut entity, "-1"), Err("inspector.error.negative"));
+  assert_eq!(InspectorField::Slippiness.apply(&mut entity, "1.5"), Err("inspector.error.range"));
+  assert_eq!(InspectorField::Health.apply(&mut entity, "ten"), Err("inspector.error.number"));
+  assert_eq!(InspectorField::Velocity.apply(&mut entity, "3"), Err("inspector.error.pair"));
+  assert_eq!(
+    InspectorField::ReceivingEntityIds.apply(&mut entity, "door"),
+    Err("inspector.error.receivers")
+  );
+  assert_eq!(entity.bounciness, 0.4);
+
+  InspectorField::Health.apply(&mut entity, " 3 ").unwrap();
+  InspectorField::Velocity.apply(&mut entity, "1.5, -2").unwrap();
+  assert_eq!(entity.health, Some(3));
+  assert_eq!(InspectorField::Velocity.text(&entity), "1.5, -2");
+  InspectorField::Health.apply(&mut entity, "").unwrap();
+  assert_eq!(entity.health, None);
+
+  for event in &["Kill", "Teleport 10, -20", "FadeTo 0, 0, 0, 255, 60", "Collect coin, 10"] {
+    InspectorField::Event.apply(&mut entity, event).unwrap();
+    assert_eq!(InspectorField::Event.text(&entity), *event);
+  }
+  InspectorField::ReceivingEntityIds.apply(&mut entity, "door, gate").unwrap();
+  InspectorField::Event.apply(&mut entity, "Dialog temple_sign").unwrap();
+  assert_eq!(InspectorField::ReceivingEntityIds.text(&entity), "door, gate");
+  assert_eq!(InspectorField::Event.apply(&mut entity, "Shake"), Err("inspector.error.event"));
+  assert_eq!(
+    InspectorField::Event.apply(&mut entity, "Dialog temple_sing"),
+    Err("inspector.error.dialog")
+  );
+
+  InspectorField::Bounciness.apply(&mut entity, "2.5").unwrap();
+  assert_eq!(InspectorField::Bounciness.text(&entity), "2.5");
+}
+
+#[test]
+fn inspector_edits_that_change_nothing_leave_no_undo_step() {
+  use super::editor_menu::LevelEntityVariant;
+
+  let mut level = Level {
+    name: None,
+    background: vec![],
+    indestructible: vec![Entity::new(0.0, 0.0, 10, 10)],
+    destructible: vec![],
+    enemies: vec![],
+    main_character: vec![],
+    effects: vec![],
+    foreground: vec![],
+  };
+  let mut history = EditorHistory::new(10);
+  let mut inspector = EntityInspector::new();
+  let entity_ref = (LevelEntityVariant::Indestructible, 0);
+  let mut edit_field = |level: &mut Level, field, text: &str| {
+    inspector.begin(level, entity_ref, FIELDS.iter().position(|other| *other == field).unwrap());
+    inspector.edit.as_mut().unwrap().text = text.to_string();
+    inspector.commit(level, &mut history, entity_ref);
+  };
+
+  edit_field(&mut level, InspectorField::Health, "3");
+  edit_field(&mut level, InspectorField::Bounciness, "0.40");
+  assert_eq!(level.indestructible[0].health, Some(3));
+
+  history.undo(&mut level);
+  assert_eq!(level.indestructible[0].health, None);
+}
@@ -0,0 +1,55 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+  Title,
+  LevelSelect,
+  Playing,
+  Paused,
+  Settings,
+  Editing,
+  LevelComplete,
+  GameOver,
+}
+
+impl GameState {
+  // The level is only simulated while it is played, every other state freezes it
+  pub fn is_simulating(self) -> bool {
+    self == GameState::Playing
+  }
+  // Title, level select and settings are drawn on their own, every other state draws the level
+  pub fn shows_level(self) -> bool {
+    !matches!(self, GameState::Title | GameState::LevelSelect | GameState::Settings)
+  }
+  // Pausing keeps the state it came from in `paused_state`, so resuming goes back to the editor
+  pub fn toggle_pause(self, paused_state: &mut GameState) -> Self {
+    match self {
+      GameState::Playing | GameState::Editing => {
+        *paused_state = self;
+        GameState::Paused
+      }
+      GameState::Paused => *paused_state,
+      other => other,
+    }
+  }
+  pub fn toggle_editing(self) -> Self {
+    match self {
+      GameState::Playing | GameState::Paused => GameState::Editing,
+      GameState::Editing => GameState::Playing,
+      other => other,
+    }
+  }
+}
+
+#[test]
+fn game_state_toggles_only_between_level_states() {
+  let mut paused_state = GameState::Playing;
+  assert_eq!(GameState::Editing.toggle_pause(&mut paused_state), GameState::Paused);
+  assert_eq!(GameState::Paused.toggle_pause(&mut paused_state), GameState::Editing);
+  assert_eq!(GameState::Playing.toggle_pause(&mut paused_state), GameState::Paused);
+  assert_eq!(GameState::Paused.toggle_pause(&mut paused_state), GameState::Playing);
+  assert_eq!(GameState::Paused.toggle_editing(), GameState::Editing);
+  assert_eq!(GameState::Editing.toggle_editing(), GameState::Playing);
+  assert_eq!(GameState::Title.toggle_pause(&mut paused_state), GameState::Title);
+  assert_eq!(GameState::GameOver.toggle_editing(), GameState::GameOver);
+  assert!(!GameState::LevelSelect.shows_level());
+  assert!(GameState::LevelComplete.shows_level());
+}
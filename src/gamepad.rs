@@ -0,0 +1,53 @@
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+// Every connected gamepad, kept open so it keeps sending events
+pub struct Gamepads {
+  subsystem: GameControllerSubsystem,
+  controllers: Vec<GameController>,
+}
+
+impl Gamepads {
+  pub fn open(subsystem: GameControllerSubsystem) -> Self {
+    let mut gamepads = Self { subsystem, controllers: vec![] };
+    for index in 0..gamepads.subsystem.num_joysticks().unwrap_or(0) {
+      gamepads.connect(index);
+    }
+    gamepads
+  }
+  pub fn controllers(&self) -> &[GameController] {
+    &self.controllers
+  }
+  // SDL also reports gamepads that were already connected at startup as added
+  fn connect(&mut self, joystick_index: u32) {
+    if !self.subsystem.is_game_controller(joystick_index) {
+      return;
+    }
+    match self.subsystem.open(joystick_index) {
+      Ok(controller) => {
+        if self.controllers.iter().all(|open| open.instance_id() != controller.instance_id()) {
+          println!("Gamepad connected: {}", controller.name());
+          self.controllers.push(controller);
+        }
+      }
+      Err(error) => println!("couldn't open gamepad {}: {}", joystick_index, error),
+    }
+  }
+  pub fn handle_event(&mut self, event: &Event) {
+    match event {
+      Event::ControllerDeviceAdded { which, .. } => self.connect(*which),
+      Event::ControllerDeviceRemoved { which, .. } => {
+        self.controllers.retain(|controller| controller.instance_id() as u32 != *which);
+      }
+      _ => {}
+    }
+  }
+  // Gamepads without rumble motors are skipped silently
+  pub fn rumble(&mut self, strength: f32, duration_millis: u32) {
+    let intensity = (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+    for controller in &mut self.controllers {
+      let _ = controller.set_rumble(intensity, intensity, duration_millis);
+    }
+  }
+}
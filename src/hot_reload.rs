@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Checking modification times is cheap, but not cheap enough for every frame
+static POLL_INTERVAL_FRAMES: u32 = 30;
+
+// Polls modification times, so it works the same on every platform
+pub struct FileWatcher {
+  modified_times: HashMap<PathBuf, Option<SystemTime>>,
+  frames_until_poll: u32,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl FileWatcher {
+  pub fn new() -> Self {
+    Self { modified_times: HashMap::new(), frames_until_poll: POLL_INTERVAL_FRAMES }
+  }
+  // Watching a file again keeps its known modification time
+  pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+    let path = path.as_ref();
+    if !self.modified_times.contains_key(path) {
+      self.modified_times.insert(path.to_path_buf(), modified_time(path));
+    }
+  }
+  // Called once per frame, returns the files that changed since the last poll
+  pub fn poll(&mut self) -> Vec<PathBuf> {
+    if self.frames_until_poll > 0 {
+      self.frames_until_poll -= 1;
+      return vec![];
+    }
+    self.frames_until_poll = POLL_INTERVAL_FRAMES;
+    self.poll_now()
+  }
+  fn poll_now(&mut self) -> Vec<PathBuf> {
+    let mut changed_paths = vec![];
+    for (path, known_time) in &mut self.modified_times {
+      let time = modified_time(path);
+      if time != *known_time {
+        *known_time = time;
+        changed_paths.push(path.clone());
+      }
+    }
+    changed_paths.sort();
+    changed_paths
+  }
+}
+
+#[test]
+fn file_watcher_reports_changed_files_once() {
+  let path = std::env::temp_dir().join(format!("file_watcher_{}.json", std::process::id()));
+  fs::write(&path, "{}").unwrap();
+
+  let mut watcher = FileWatcher::new();
+  watcher.watch(&path);
+  assert!(watcher.poll_now().is_empty());
+
+  fs::remove_file(&path).unwrap();
+  assert_eq!(watcher.poll_now(), vec![path.clone()]);
+  assert!(watcher.poll_now().is_empty());
+}
@@ -0,0 +1,209 @@
+use super::level_select::format_time;
+use super::localization::Localization;
+use super::text::{measure_text_line, show_text_line, Font};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, WindowCanvas};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+static LETTER_GAP: f32 = 1.1;
+static ICON_GAP: i32 = 4;
+static TEXT_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum HudAnchor {
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum HudElementKind {
+  Health,
+  Ammo,
+  Items,
+  Score,
+  Timer,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HudElement {
+  pub kind: HudElementKind,
+  pub anchor: HudAnchor,
+}
+
+// Elements sharing an anchor are stacked in the order they are listed, away from the corner
+#[derive(Debug, Deserialize)]
+pub struct HudConfig {
+  pub elements: Vec<HudElement>,
+  pub margin: i32,
+  pub row_gap: i32,
+  pub letter_scale: u8,
+  pub health_per_icon: i32,
+  pub health_icon: (i32, i32, u32, u32),
+  pub ammo_icon: (i32, i32, u32, u32),
+  pub item_icon: (i32, i32, u32, u32),
+  #[serde(default)]
+  pub item_icons: HashMap<String, (i32, i32, u32, u32)>,
+}
+
+impl HudConfig {
+  pub fn deserialize(serialized: String) -> Result<Self, String> {
+    let config: Self = serde_json::from_str(&serialized).map_err(|error| error.to_string())?;
+    if config.health_per_icon < 1 {
+      return Err(format!(
+        "health_per_icon is {}, it has to be at least 1",
+        config.health_per_icon
+      ));
+    }
+    Ok(config)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+  pub score: u32,
+  pub items: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+  pub fn collect(&mut self, item: &str, score: u32) {
+    *self.items.entry(item.to_string()).or_insert(0) += 1;
+    self.score += score;
+  }
+}
+
+pub struct HudValues<'a> {
+  pub health: Option<i32>,
+  pub ammo: Option<u32>,
+  pub inventory: &'a Inventory,
+  pub time: Duration,
+}
+
+// A row of icons followed by an optional label
+struct HudRow {
+  icons: Vec<(i32, i32, u32, u32)>,
+  label: String,
+}
+
+fn rows_for(
+  config: &HudConfig,
+  kind: HudElementKind,
+  values: &HudValues,
+  localization: &Localization,
+) -> Vec<HudRow> {
+  match kind {
+    HudElementKind::Health => match values.health {
+      Some(health) if health > 0 => {
+        let icon_count = (health + config.health_per_icon - 1) / config.health_per_icon;
+        vec![HudRow { icons: vec![config.health_icon; icon_count as usize], label: String::new() }]
+      }
+      _ => vec![],
+    },
+    HudElementKind::Ammo => match values.ammo {
+      Some(ammo) => vec![HudRow { icons: vec![config.ammo_icon], label: ammo.to_string() }],
+      None => vec![],
+    },
+    HudElementKind::Items => values
+      .inventory
+      .items
+      .iter()
+      .map(|(item, count)| HudRow {
+        icons: vec![*config.item_icons.get(item).unwrap_or(&config.item_icon)],
+        label: format!("x{}", count),
+      })
+      .collect(),
+    HudElementKind::Score => vec![HudRow {
+      icons: vec![],
+      label: localization.format("hud.score", &[("score", &values.inventory.score.to_string())]),
+    }],
+    HudElementKind::Timer => vec![HudRow { icons: vec![], label: format_time(values.time) }],
+  }
+}
+
+// Positions are in logical coordinates, so corners stay corners however the window is scaled
+pub fn draw_hud(
+  canvas: &mut WindowCanvas,
+  font: &mut Font,
+  ui_texture: &Texture,
+  localization: &Localization,
+  config: &HudConfig,
+  values: &HudValues,
+  dimensions: (u16, u16),
+) {
+  let mut offsets: HashMap<(bool, bool), i32> = HashMap::new();
+  for element in &config.elements {
+    let is_right =
+      element.anchor == HudAnchor::TopRight || element.anchor == HudAnchor::BottomRight;
+    let is_bottom =
+      element.anchor == HudAnchor::BottomLeft || element.anchor == HudAnchor::BottomRight;
+
+    for row in rows_for(config, element.kind, values, localization) {
+      let (label_width, label_height) =
+        measure_text_line(font, &row.label, config.letter_scale, LETTER_GAP);
+      let icons_width = row.icons.iter().map(|icon| icon.2 as i32 + ICON_GAP).sum::<i32>();
+      let row_width = icons_width + label_width as i32;
+      let row_height =
+        row.icons.iter().map(|icon| icon.3 as i32).fold(label_height as i32, i32::max);
+
+      let offset = offsets.entry((is_right, is_bottom)).or_insert(0);
+      let x =
+        if is_right { dimensions.0 as i32 - config.margin - row_width } else { config.margin };
+      let y = if is_bottom {
+        dimensions.1 as i32 - config.margin - *offset - row_height
+      } else {
+        config.margin + *offset
+      };
+      *offset += row_height + config.row_gap;
+
+      let mut icon_x = x;
+      for icon in &row.icons {
+        canvas
+          .copy(
+            ui_texture,
+            Some(Rect::new(icon.0, icon.1, icon.2, icon.3)),
+            Some(Rect::new(icon_x, y + (row_height - icon.3 as i32) / 2, icon.2, icon.3)),
+          )
+          .unwrap();
+        icon_x += icon.2 as i32 + ICON_GAP;
+      }
+      show_text_line(
+        canvas,
+        font,
+        &row.label,
+        (icon_x, y + (row_height - label_height as i32) / 2),
+        config.letter_scale,
+        LETTER_GAP,
+        TEXT_COLOR,
+      );
+    }
+  }
+}
+
+#[test]
+fn inventory_counts_items_and_adds_score() {
+  let mut inventory = Inventory::default();
+  inventory.collect("coin", 10);
+  inventory.collect("coin", 10);
+  inventory.collect("gem", 50);
+
+  assert_eq!(inventory.items["coin"], 2);
+  assert_eq!(inventory.items["gem"], 1);
+  assert_eq!(inventory.score, 70);
+}
+
+#[test]
+fn hud_config_needs_health_for_every_icon() {
+  let serialized = std::fs::read_to_string("config/hud.json").unwrap();
+  assert!(HudConfig::deserialize(serialized.clone()).is_ok());
+
+  let with_zero_health_per_icon =
+    serialized.replace("\"health_per_icon\": 1", "\"health_per_icon\": 0");
+  assert_eq!(
+    HudConfig::deserialize(with_zero_health_per_icon).err().unwrap(),
+    "health_per_icon is 0, it has to be at least 1"
+  );
+}
@@ -0,0 +1,113 @@
+use super::controls::Action;
+use super::settings::write_config;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+// The input of one tick without any devices. Pressed actions map to how far they are pressed,
+// from 0 to 1, released actions are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+  #[serde(default)]
+  pub actions: BTreeMap<Action, f32>,
+  // Absolute aim in radians, from a stick or the mouse
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub aim_direction: Option<f32>,
+}
+
+// Anything that can drive the game for a tick: devices, replays, scripts or bots
+pub trait InputProducer {
+  // `live` is what the keyboard, mouse and gamepads report this tick
+  fn next_state(&mut self, live: InputState) -> InputState;
+}
+
+pub struct LiveInput;
+
+impl InputProducer for LiveInput {
+  fn next_state(&mut self, live: InputState) -> InputState {
+    live
+  }
+}
+
+// Runs of identical ticks keep replay files short
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputStep {
+  pub ticks: u32,
+  pub state: InputState,
+}
+
+// Plays back steps tick by tick, live input takes over once they run out
+pub struct ScriptedInput {
+  steps: VecDeque<InputStep>,
+}
+
+impl ScriptedInput {
+  pub fn new(steps: Vec<InputStep>) -> Self {
+    Self { steps: steps.into() }
+  }
+  pub fn load(file_path: &str) -> Result<Self, String> {
+    let serialized = fs::read_to_string(file_path).map_err(|error| error.to_string())?;
+    let steps = serde_json::from_str(&serialized).map_err(|error| error.to_string())?;
+    Ok(Self::new(steps))
+  }
+}
+
+impl InputProducer for ScriptedInput {
+  fn next_state(&mut self, live: InputState) -> InputState {
+    while let Some(step) = self.steps.front_mut() {
+      if step.ticks > 0 {
+        step.ticks -= 1;
+        return step.state.clone();
+      }
+      self.steps.pop_front();
+    }
+    live
+  }
+}
+
+pub struct InputRecorder {
+  steps: Vec<InputStep>,
+}
+
+impl InputRecorder {
+  pub fn new() -> Self {
+    Self { steps: vec![] }
+  }
+  pub fn record(&mut self, state: &InputState) {
+    match self.steps.last_mut() {
+      Some(step) if step.state == *state => step.ticks += 1,
+      _ => self.steps.push(InputStep { ticks: 1, state: state.clone() }),
+    }
+  }
+  pub fn save(&self, file_path: &str) {
+    if let Some(directory) = Path::new(file_path).parent() {
+      fs::create_dir_all(directory).unwrap();
+    }
+    write_config(file_path, &self.steps);
+  }
+}
+
+#[test]
+fn recorded_input_plays_back_tick_by_tick() {
+  let pressed = |action, aim_direction| InputState {
+    actions: vec![(action, 1.0)].into_iter().collect(),
+    aim_direction,
+  };
+  let jump = pressed(Action::Jump, None);
+  let aim = pressed(Action::Shoot, Some(1.5));
+
+  let mut recorder = InputRecorder::new();
+  for state in &[jump.clone(), jump.clone(), aim.clone()] {
+    recorder.record(state);
+  }
+  assert_eq!(recorder.steps.len(), 2);
+
+  let serialized = serde_json::to_string(&recorder.steps).unwrap();
+  let mut replay = ScriptedInput::new(serde_json::from_str(&serialized).unwrap());
+  let live = pressed(Action::Left, None);
+  assert_eq!(replay.next_state(live.clone()), jump);
+  assert_eq!(replay.next_state(live.clone()), jump);
+  assert_eq!(replay.next_state(live.clone()), aim);
+  assert_eq!(replay.next_state(live.clone()), live);
+}
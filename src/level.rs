@@ -1,10 +1,13 @@
 use super::camera::Camera;
-use super::entity::Entity;
-use sdl2::render::{Texture, WindowCanvas};
+use super::entity::{Entity, EventType};
+use sdl2::render::{Canvas, RenderTarget, Texture};
 use serde::{Deserialize, Serialize};
+use std::fs;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Level {
+  #[serde(default)]
+  pub name: Option<String>,
   pub background: Vec<Entity>,
   pub indestructible: Vec<Entity>,
   pub destructible: Vec<Entity>,
@@ -22,6 +25,43 @@ impl Level {
     Level::next_container_state(&mut self.indestructible, &entities);
     Level::next_container_state(&mut self.enemies, &entities);
   }
+  // Events that the level can't handle by itself, like camera effects and dialogs
+  pub fn take_triggered_events(&mut self) -> Vec<EventType> {
+    let mut triggered_events = vec![];
+    for container in [
+      &mut self.main_character,
+      &mut self.effects,
+      &mut self.destructible,
+      &mut self.indestructible,
+      &mut self.enemies,
+    ] {
+      for entity in container.iter_mut() {
+        triggered_events.append(&mut entity.triggered_events);
+      }
+    }
+    triggered_events
+  }
+  // Collectibles disappear once a main character has touched them
+  pub fn remove_collected_items(&mut self) {
+    let main_character = &self.main_character;
+    let is_collected = |entity: &Entity| match &entity.event {
+      Some(event) => {
+        if let EventType::Collect { .. } = event.event_type {
+          main_character
+            .iter()
+            .any(|collector| collector.is_triggering(entity) && collector.is_inside_entity(entity))
+        } else {
+          false
+        }
+      }
+      None => false,
+    };
+    for container in
+      [&mut self.effects, &mut self.destructible, &mut self.indestructible, &mut self.enemies]
+    {
+      container.retain(|entity| !is_collected(entity));
+    }
+  }
   fn next_container_state(container: &mut Vec<Entity>, entities: &Vec<Entity>) {
     container.drain_filter(|entity| {
       entity.next_state(&entities);
@@ -29,7 +69,13 @@ impl Level {
     });
   }
 
-  pub fn draw(&self, camera: &mut Camera, canvas: &mut WindowCanvas, texture: &Texture) {
+  pub fn draw<T: RenderTarget>(
+    &self,
+    camera: &mut Camera,
+    canvas: &mut Canvas<T>,
+    texture: &Texture,
+  ) {
+    let camera = &mut camera.with_effects();
     Level::draw_container(&self.background, camera, canvas, texture);
     Level::draw_container(&self.indestructible, camera, canvas, texture);
     Level::draw_container(&self.destructible, camera, canvas, texture);
@@ -38,10 +84,10 @@ impl Level {
     Level::draw_container(&self.effects, camera, canvas, texture);
     Level::draw_container(&self.foreground, camera, canvas, texture);
   }
-  fn draw_container(
+  fn draw_container<T: RenderTarget>(
     container: &Vec<Entity>,
     camera: &mut Camera,
-    canvas: &mut WindowCanvas,
+    canvas: &mut Canvas<T>,
     texture: &Texture,
   ) {
     for entity in container {
@@ -54,4 +100,15 @@ impl Level {
   pub fn deserialize(serialized: String) -> Self {
     serde_json::from_str(&serialized).unwrap()
   }
+  pub fn path(name: &str) -> String {
+    format!("assets/levels/{}.json", name)
+  }
+  pub fn load(name: &str) -> Self {
+    Self::deserialize(fs::read_to_string(Self::path(name)).unwrap())
+  }
+  // Like `load`, but a broken file is reported instead of ending the game
+  pub fn try_load(name: &str) -> Result<Self, String> {
+    let serialized = fs::read_to_string(Self::path(name)).map_err(|error| error.to_string())?;
+    serde_json::from_str(&serialized).map_err(|error| error.to_string())
+  }
 }
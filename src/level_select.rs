@@ -0,0 +1,181 @@
+use super::localization::Localization;
+use super::settings::write_config;
+use super::text::{measure_text_line, show_text_line, Font};
+use sdl2::pixels::Color;
+use sdl2::render::WindowCanvas;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+static LEVELS_DIRECTORY: &str = "assets/levels";
+static BEST_TIMES_PATH: &str = "saves/best_times.json";
+
+static LETTER_SCALE: u8 = 2;
+static LETTER_GAP: f32 = 1.1;
+static ROW_HEIGHT: i32 = 28;
+static LIST_TOP: i32 = 90;
+
+static TITLE_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static SELECTED_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static LEVEL_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+
+// Best times in milliseconds, keyed by the level file name without extension
+pub struct BestTimes {
+  times: HashMap<String, u64>,
+}
+
+impl BestTimes {
+  pub fn load() -> Self {
+    let times = match fs::read_to_string(BEST_TIMES_PATH) {
+      Ok(serialized) => serde_json::from_str(&serialized).unwrap_or_default(),
+      Err(_) => HashMap::new(),
+    };
+    Self { times }
+  }
+  pub fn get(&self, level_name: &str) -> Option<Duration> {
+    self.times.get(level_name).map(|millis| Duration::from_millis(*millis))
+  }
+  // Returns true and saves the times if `time` beats the previous best time
+  pub fn record(&mut self, level_name: &str, time: Duration) -> bool {
+    let millis = time.as_millis() as u64;
+    if matches!(self.times.get(level_name), Some(best) if *best <= millis) {
+      return false;
+    }
+    self.times.insert(level_name.to_string(), millis);
+    // The saves directory isn't shipped, it is made with the first best time
+    if let Some(directory) = Path::new(BEST_TIMES_PATH).parent() {
+      if let Err(error) = fs::create_dir_all(directory) {
+        println!("couldn't save {}: {}", BEST_TIMES_PATH, error);
+        return true;
+      }
+    }
+    write_config(BEST_TIMES_PATH, &self.times);
+    true
+  }
+}
+
+pub fn format_time(time: Duration) -> String {
+  let millis = time.as_millis();
+  format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+// Only the name is needed for the list, the rest of the level is ignored
+#[derive(Deserialize)]
+struct LevelHeader {
+  name: Option<String>,
+}
+
+pub struct LevelSummary {
+  pub file_name: String,
+  pub name: String,
+  pub best_time: Option<Duration>,
+}
+
+pub struct LevelSelect {
+  pub levels: Vec<LevelSummary>,
+  selected: usize,
+}
+
+impl LevelSelect {
+  pub fn load(best_times: &BestTimes) -> Self {
+    let mut paths = fs::read_dir(LEVELS_DIRECTORY)
+      .unwrap()
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+      .collect::<Vec<_>>();
+    paths.sort();
+
+    let levels = paths
+      .iter()
+      .filter_map(|path| {
+        let file_name = path.file_stem()?.to_string_lossy().to_string();
+        let header: LevelHeader = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        Some(LevelSummary {
+          name: header.name.unwrap_or_else(|| file_name.clone()),
+          best_time: best_times.get(&file_name),
+          file_name,
+        })
+      })
+      .collect();
+    Self { levels, selected: 0 }
+  }
+  pub fn select_previous(&mut self) {
+    if self.selected > 0 {
+      self.selected -= 1;
+    }
+  }
+  pub fn select_next(&mut self) {
+    if self.selected + 1 < self.levels.len() {
+      self.selected += 1;
+    }
+  }
+  pub fn select(&mut self, file_name: &str) {
+    if let Some(index) = self.levels.iter().position(|level| level.file_name == file_name) {
+      self.selected = index;
+    }
+  }
+  pub fn selected_level(&self) -> Option<&LevelSummary> {
+    self.levels.get(self.selected)
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    dimensions: (u16, u16),
+  ) {
+    let title = localization.get("level_select.title");
+    let (title_width, _) = measure_text_line(font, title, LETTER_SCALE, LETTER_GAP);
+    show_text_line(
+      canvas,
+      font,
+      title,
+      ((dimensions.0 as i32 - title_width as i32) / 2, LIST_TOP / 2),
+      LETTER_SCALE,
+      LETTER_GAP,
+      TITLE_COLOR,
+    );
+
+    let left = dimensions.0 as i32 / 6;
+    let right = dimensions.0 as i32 - left;
+    for (index, level) in self.levels.iter().enumerate() {
+      let y = LIST_TOP + index as i32 * ROW_HEIGHT;
+      let (prefix, color) =
+        if index == self.selected { ("> ", SELECTED_COLOR) } else { ("  ", LEVEL_COLOR) };
+      show_text_line(
+        canvas,
+        font,
+        &format!("{}{}", prefix, level.name),
+        (left, y),
+        LETTER_SCALE,
+        LETTER_GAP,
+        color,
+      );
+      let best_time = match level.best_time {
+        Some(time) => {
+          localization.format("level_select.best_time", &[("time", &format_time(time))])
+        }
+        None => localization.get("level_select.no_best_time").to_string(),
+      };
+      let (best_time_width, _) = measure_text_line(font, &best_time, LETTER_SCALE, LETTER_GAP);
+      show_text_line(
+        canvas,
+        font,
+        &best_time,
+        (right - best_time_width as i32, y),
+        LETTER_SCALE,
+        LETTER_GAP,
+        color,
+      );
+    }
+  }
+}
+
+#[test]
+fn format_time_shows_minutes_seconds_and_millis() {
+  assert_eq!(format_time(Duration::from_millis(83_456)), "1:23.456");
+  assert_eq!(format_time(Duration::from_millis(5_007)), "0:05.007");
+}
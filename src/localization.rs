@@ -0,0 +1,103 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+pub static DEFAULT_LANGUAGE: &str = "en";
+static STRINGS_DIRECTORY: &str = "assets/strings";
+
+pub struct Localization {
+  strings: HashMap<String, String>,
+  default_strings: HashMap<String, String>,
+}
+
+fn load_table(language: &str) -> HashMap<String, String> {
+  match fs::read_to_string(format!("{}/{}.json", STRINGS_DIRECTORY, language)) {
+    Ok(serialized) => serde_json::from_str(&serialized).unwrap(),
+    Err(_) => {
+      println!("No strings for language '{}', using '{}'", language, DEFAULT_LANGUAGE);
+      HashMap::new()
+    }
+  }
+}
+
+// Replaces `{name}` placeholders, markup like `{#ff0000}` and `{/}` is left untouched
+pub fn fill_placeholders(text: &str, arguments: &[(&str, &str)]) -> String {
+  let mut filled = text.to_string();
+  for (name, value) in arguments {
+    filled = filled.replace(&format!("{{{}}}", name), value);
+  }
+  filled
+}
+
+impl Localization {
+  pub fn load(language: &str) -> Self {
+    Self { strings: load_table(language), default_strings: load_table(DEFAULT_LANGUAGE) }
+  }
+  // Falls back to the default language and then to the id itself, so missing strings stay visible
+  pub fn get<'a>(&'a self, id: &'a str) -> &'a str {
+    self.strings.get(id).or_else(|| self.default_strings.get(id)).map_or(id, String::as_str)
+  }
+  pub fn format(&self, id: &str, arguments: &[(&str, &str)]) -> String {
+    fill_placeholders(self.get(id), arguments)
+  }
+}
+
+// Returns the keys every language is missing compared to all other languages, and the keys that
+// no file in `reference_directories` mentions in quotes
+pub fn check_string_tables(reference_directories: &[&str]) -> (Vec<(String, String)>, Vec<String>) {
+  let mut tables = vec![];
+  let mut paths = fs::read_dir(STRINGS_DIRECTORY)
+    .unwrap()
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+    .collect::<Vec<_>>();
+  paths.sort();
+  for path in paths {
+    let language = path.file_stem().unwrap().to_string_lossy().to_string();
+    tables.push((language.clone(), load_table(&language)));
+  }
+
+  let all_keys =
+    tables.iter().flat_map(|(_, table)| table.keys().cloned()).collect::<BTreeSet<_>>();
+
+  let mut missing_keys = vec![];
+  for (language, table) in &tables {
+    for key in all_keys.iter().filter(|key| !table.contains_key(*key)) {
+      missing_keys.push((language.clone(), key.clone()));
+    }
+  }
+
+  let mut references = String::new();
+  for directory in reference_directories {
+    if let Ok(entries) = fs::read_dir(Path::new(directory)) {
+      for entry in entries.filter_map(|entry| entry.ok()) {
+        references.push_str(&fs::read_to_string(entry.path()).unwrap_or_default());
+      }
+    }
+  }
+  let unused_keys = all_keys
+    .into_iter()
+    .filter(|key| !references.contains(&format!("\"{}\"", key)))
+    .collect::<Vec<_>>();
+
+  (missing_keys, unused_keys)
+}
+
+#[test]
+fn localization_falls_back_to_default_language_and_id() {
+  let mut strings = HashMap::new();
+  strings.insert("menu.paused".to_string(), "Pausiert".to_string());
+  let mut default_strings = HashMap::new();
+  default_strings.insert("menu.paused".to_string(), "Paused".to_string());
+  default_strings
+    .insert("menu.paused_hint".to_string(), "Press {key} to {#ff0000}resume{/}".to_string());
+  let localization = Localization { strings, default_strings };
+
+  assert_eq!(localization.get("menu.paused"), "Pausiert");
+  assert_eq!(
+    localization.format("menu.paused_hint", &[("key", "Escape")]),
+    "Press Escape to {#ff0000}resume{/}"
+  );
+  assert_eq!(localization.get("menu.unknown"), "menu.unknown");
+}
@@ -2,5 +2,24 @@
 mod app;
 
 fn main() {
-  app::run("temples", "temples");
+  let arguments: Vec<String> = std::env::args().collect();
+  match arguments.get(1).map(String::as_str) {
+    Some("--capture") => {
+      let frame_count = arguments.get(2).and_then(|count| count.parse().ok()).unwrap_or(300);
+      app::capture("temples", "temples", frame_count);
+    }
+    Some("--check-glyphs") => {
+      if !app::check_glyphs("default") {
+        std::process::exit(1);
+      }
+    }
+    Some("--check-strings") => {
+      if !app::check_strings() {
+        std::process::exit(1);
+      }
+    }
+    Some("--replay") => app::run("temples", "temples", arguments.get(2).map(String::as_str), false),
+    Some("--record") => app::run("temples", "temples", None, true),
+    _ => app::run("temples", "temples", None, false),
+  }
 }
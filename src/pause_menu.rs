@@ -0,0 +1,228 @@
+use super::localization::Localization;
+use super::text::{
+  measure_text, measure_text_line, show_text_line, Font, TextAlignment, TextLayout,
+};
+use super::ui::{draw_text_panel, PANEL_PADDING as HINT_PANEL_PADDING};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+static LETTER_SCALE: u8 = 2;
+static LETTER_GAP: f32 = 1.1;
+static PANEL_PADDING: i32 = 12;
+static ROW_GAP: i32 = 6;
+static HINT_MARGIN: i32 = 8;
+
+static PANEL_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static BORDER_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static TITLE_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static SELECTED_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static SELECTED_BACKGROUND_COLOR: Color = Color { r: 70, g: 40, b: 36, a: 0xff };
+static ENTRY_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+static DISABLED_COLOR: Color = Color { r: 75, g: 75, b: 70, a: 0xff };
+static HINT_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PauseMenuEntry {
+  Resume,
+  RestartLevel,
+  RestartFromCheckpoint,
+  Settings,
+  ReturnToEditor,
+  Quit,
+}
+
+static ENTRIES: [PauseMenuEntry; 6] = [
+  PauseMenuEntry::Resume,
+  PauseMenuEntry::RestartLevel,
+  PauseMenuEntry::RestartFromCheckpoint,
+  PauseMenuEntry::Settings,
+  PauseMenuEntry::ReturnToEditor,
+  PauseMenuEntry::Quit,
+];
+
+fn entry_string_id(entry: PauseMenuEntry) -> &'static str {
+  match entry {
+    PauseMenuEntry::Resume => "menu.resume",
+    PauseMenuEntry::RestartLevel => "menu.restart_level",
+    PauseMenuEntry::RestartFromCheckpoint => "menu.restart_checkpoint",
+    PauseMenuEntry::Settings => "menu.settings",
+    PauseMenuEntry::ReturnToEditor => "menu.return_to_editor",
+    PauseMenuEntry::Quit => "menu.quit",
+  }
+}
+
+pub struct PauseMenu {
+  selected: usize,
+  disabled_entries: Vec<PauseMenuEntry>,
+}
+
+impl PauseMenu {
+  pub fn new() -> Self {
+    Self { selected: 0, disabled_entries: vec![] }
+  }
+  pub fn reset(&mut self) {
+    self.selected = 0;
+  }
+  pub fn set_enabled(&mut self, entry: PauseMenuEntry, is_enabled: bool) {
+    self.disabled_entries.retain(|disabled_entry| *disabled_entry != entry);
+    if !is_enabled {
+      self.disabled_entries.push(entry);
+      if ENTRIES[self.selected] == entry {
+        self.select_next();
+      }
+    }
+  }
+  fn is_enabled(&self, entry: PauseMenuEntry) -> bool {
+    !self.disabled_entries.contains(&entry)
+  }
+  pub fn selected_entry(&self) -> PauseMenuEntry {
+    ENTRIES[self.selected]
+  }
+  // Disabled entries are skipped, the selection stops at the first and last entry
+  pub fn select_previous(&mut self) {
+    if let Some(index) = (0..self.selected).rev().find(|index| self.is_enabled(ENTRIES[*index])) {
+      self.selected = index;
+    }
+  }
+  pub fn select_next(&mut self) {
+    if let Some(index) =
+      (self.selected + 1..ENTRIES.len()).find(|index| self.is_enabled(ENTRIES[*index]))
+    {
+      self.selected = index;
+    }
+  }
+  // The panel is centered, the title takes the first row and every entry one row below it
+  fn layout(
+    &self,
+    font: &Font,
+    localization: &Localization,
+    dimensions: (u16, u16),
+  ) -> (Rect, Vec<Rect>) {
+    let title_size =
+      measure_text_line(font, localization.get("menu.paused"), LETTER_SCALE, LETTER_GAP);
+    let row_height = title_size.1 as i32 + ROW_GAP;
+    let content_width = ENTRIES
+      .iter()
+      .map(|entry| {
+        measure_text_line(font, localization.get(entry_string_id(*entry)), LETTER_SCALE, LETTER_GAP)
+          .0
+      })
+      .fold(title_size.0, u32::max) as i32;
+
+    let panel_width = content_width + PANEL_PADDING * 2;
+    let panel_height = row_height * (ENTRIES.len() as i32 + 1) + PANEL_PADDING * 2;
+    let panel_rect = Rect::new(
+      (dimensions.0 as i32 - panel_width) / 2,
+      (dimensions.1 as i32 - panel_height) / 2,
+      panel_width as u32,
+      panel_height as u32,
+    );
+    let entry_rects = (0..ENTRIES.len() as i32)
+      .map(|index| {
+        Rect::new(
+          panel_rect.x() + PANEL_PADDING / 2,
+          panel_rect.y() + PANEL_PADDING + row_height * (index + 1) - ROW_GAP / 2,
+          (content_width + PANEL_PADDING) as u32,
+          row_height as u32,
+        )
+      })
+      .collect();
+    (panel_rect, entry_rects)
+  }
+  // Selects the entry under the mouse, returns it if it can be chosen
+  pub fn hover(
+    &mut self,
+    font: &Font,
+    localization: &Localization,
+    dimensions: (u16, u16),
+    position: (i32, i32),
+  ) -> Option<PauseMenuEntry> {
+    let (_, entry_rects) = self.layout(font, localization, dimensions);
+    let index = entry_rects.iter().position(|rect| rect.contains_point(position))?;
+    if !self.is_enabled(ENTRIES[index]) {
+      return None;
+    }
+    self.selected = index;
+    Some(ENTRIES[index])
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    pause_key_name: &str,
+    dimensions: (u16, u16),
+  ) {
+    let (panel_rect, entry_rects) = self.layout(font, localization, dimensions);
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(PANEL_COLOR);
+    canvas.fill_rect(panel_rect).unwrap();
+    canvas.set_draw_color(BORDER_COLOR);
+    canvas.draw_rect(panel_rect).unwrap();
+    canvas.set_draw_color(SELECTED_BACKGROUND_COLOR);
+    canvas.fill_rect(entry_rects[self.selected]).unwrap();
+    canvas.set_draw_color(original_color);
+
+    show_text_line(
+      canvas,
+      font,
+      localization.get("menu.paused"),
+      (panel_rect.x() + PANEL_PADDING, panel_rect.y() + PANEL_PADDING),
+      LETTER_SCALE,
+      LETTER_GAP,
+      TITLE_COLOR,
+    );
+    for (index, (entry, rect)) in ENTRIES.iter().zip(entry_rects).enumerate() {
+      let color = if !self.is_enabled(*entry) {
+        DISABLED_COLOR
+      } else if index == self.selected {
+        SELECTED_COLOR
+      } else {
+        ENTRY_COLOR
+      };
+      show_text_line(
+        canvas,
+        font,
+        localization.get(entry_string_id(*entry)),
+        (rect.x() + PANEL_PADDING / 2, rect.y() + ROW_GAP / 2),
+        LETTER_SCALE,
+        LETTER_GAP,
+        color,
+      );
+    }
+
+    let hint = localization.format("menu.paused_hint", &[("key", pause_key_name)]);
+    let hint_layout = TextLayout::new(1).alignment(TextAlignment::Center);
+    let (hint_width, _) = measure_text(font, &hint, &hint_layout);
+    draw_text_panel(
+      canvas,
+      font,
+      &hint,
+      (
+        (dimensions.0 as i32 - hint_width as i32) / 2 - HINT_PANEL_PADDING,
+        panel_rect.y() + panel_rect.height() as i32 + HINT_MARGIN,
+      ),
+      &hint_layout,
+      HINT_COLOR,
+    );
+  }
+}
+
+#[test]
+fn pause_menu_skips_disabled_entries() {
+  let mut pause_menu = PauseMenu::new();
+  pause_menu.set_enabled(PauseMenuEntry::RestartFromCheckpoint, false);
+  pause_menu.set_enabled(PauseMenuEntry::Settings, false);
+
+  pause_menu.select_next();
+  assert_eq!(pause_menu.selected_entry(), PauseMenuEntry::RestartLevel);
+  pause_menu.select_next();
+  assert_eq!(pause_menu.selected_entry(), PauseMenuEntry::ReturnToEditor);
+  pause_menu.select_next();
+  pause_menu.select_next();
+  assert_eq!(pause_menu.selected_entry(), PauseMenuEntry::Quit);
+  pause_menu.select_previous();
+  pause_menu.select_previous();
+  assert_eq!(pause_menu.selected_entry(), PauseMenuEntry::RestartLevel);
+}
@@ -0,0 +1,200 @@
+use super::text::{show_text_line, Font};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use std::collections::VecDeque;
+use std::fs;
+use std::time::{Duration, Instant};
+
+static FRAME_BUDGET_MICROS: u128 = 16_000;
+static GRAPH_HEIGHT: u32 = 60;
+static GRAPH_BAR_WIDTH: u32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FramePhase {
+  Input,
+  EntityCommands,
+  LevelState,
+  Camera,
+  Draw,
+  Present,
+}
+
+static PHASES: [FramePhase; 6] = [
+  FramePhase::Input,
+  FramePhase::EntityCommands,
+  FramePhase::LevelState,
+  FramePhase::Camera,
+  FramePhase::Draw,
+  FramePhase::Present,
+];
+
+fn phase_name(phase: FramePhase) -> &'static str {
+  match phase {
+    FramePhase::Input => "input",
+    FramePhase::EntityCommands => "entity commands",
+    FramePhase::LevelState => "level state",
+    FramePhase::Camera => "camera",
+    FramePhase::Draw => "draw",
+    FramePhase::Present => "present",
+  }
+}
+
+fn phase_color(phase: FramePhase) -> Color {
+  match phase {
+    FramePhase::Input => Color { r: 120, g: 120, b: 255, a: 0xff },
+    FramePhase::EntityCommands => Color { r: 120, g: 255, b: 120, a: 0xff },
+    FramePhase::LevelState => Color { r: 255, g: 200, b: 60, a: 0xff },
+    FramePhase::Camera => Color { r: 60, g: 220, b: 220, a: 0xff },
+    FramePhase::Draw => Color { r: 255, g: 100, b: 100, a: 0xff },
+    FramePhase::Present => Color { r: 220, g: 120, b: 255, a: 0xff },
+  }
+}
+
+pub struct Profiler {
+  history: VecDeque<[Duration; 6]>,
+  history_length: usize,
+  current_frame: [Duration; 6],
+  phase_start: Instant,
+}
+
+impl Profiler {
+  pub fn new(history_length: usize) -> Self {
+    Self {
+      history: VecDeque::with_capacity(history_length),
+      history_length,
+      current_frame: [Duration::from_secs(0); 6],
+      phase_start: Instant::now(),
+    }
+  }
+  pub fn start_frame(&mut self) {
+    self.current_frame = [Duration::from_secs(0); 6];
+    self.phase_start = Instant::now();
+  }
+  pub fn end_phase(&mut self, phase: FramePhase) {
+    let now = Instant::now();
+    self.current_frame[phase as usize] += now - self.phase_start;
+    self.phase_start = now;
+  }
+  pub fn end_frame(&mut self) {
+    if self.history.len() == self.history_length {
+      self.history.pop_front();
+    }
+    self.history.push_back(self.current_frame);
+  }
+  fn average(&self, phase: FramePhase) -> Duration {
+    if self.history.is_empty() {
+      return Duration::from_secs(0);
+    }
+    self.history.iter().map(|frame| frame[phase as usize]).sum::<Duration>()
+      / self.history.len() as u32
+  }
+  pub fn draw(&self, canvas: &mut WindowCanvas, font: &mut Font, position: (i32, i32)) {
+    let original_color = canvas.draw_color();
+    let graph_bottom = position.1 + GRAPH_HEIGHT as i32;
+
+    for (index, frame) in self.history.iter().enumerate() {
+      let x = position.0 + (index as u32 * GRAPH_BAR_WIDTH) as i32;
+      let mut y = graph_bottom;
+      for phase in PHASES.iter() {
+        let height = (frame[*phase as usize].as_micros() * GRAPH_HEIGHT as u128
+          / (FRAME_BUDGET_MICROS * 2)) as u32;
+        if height == 0 {
+          continue;
+        }
+        y -= height as i32;
+        canvas.set_draw_color(phase_color(*phase));
+        canvas.fill_rect(Rect::new(x, y, GRAPH_BAR_WIDTH, height)).unwrap();
+      }
+    }
+
+    // Frame budget line, the graph shows up to two budgets
+    canvas.set_draw_color(Color { r: 255, g: 255, b: 255, a: 0xff });
+    canvas
+      .draw_line(
+        (position.0, graph_bottom - GRAPH_HEIGHT as i32 / 2),
+        (
+          position.0 + (self.history_length as u32 * GRAPH_BAR_WIDTH) as i32,
+          graph_bottom - GRAPH_HEIGHT as i32 / 2,
+        ),
+      )
+      .unwrap();
+
+    for (index, phase) in PHASES.iter().enumerate() {
+      show_text_line(
+        canvas,
+        font,
+        &format!(
+          "{}: {:.2}ms",
+          phase_name(*phase),
+          self.average(*phase).as_micros() as f32 / 1000.0
+        ),
+        (position.0, graph_bottom + 6 + index as i32 * 12),
+        1,
+        1.1,
+        phase_color(*phase),
+      );
+    }
+    canvas.set_draw_color(original_color);
+  }
+  // One row of microseconds per frame, oldest first, under a header with the phase names
+  fn csv(&self) -> String {
+    let mut csv = PHASES.iter().map(|phase| phase_name(*phase)).collect::<Vec<_>>().join(",");
+    csv.push('\n');
+    for frame in &self.history {
+      csv.push_str(
+        &frame
+          .iter()
+          .map(|duration| duration.as_micros().to_string())
+          .collect::<Vec<_>>()
+          .join(","),
+      );
+      csv.push('\n');
+    }
+    csv
+  }
+  pub fn dump_csv(&self, file_path: &str) {
+    if let Some(directory) = std::path::Path::new(file_path).parent() {
+      if let Err(error) = fs::create_dir_all(directory) {
+        println!("couldn't save {}: {}", file_path, error);
+        return;
+      }
+    }
+    match fs::write(file_path, self.csv()) {
+      Err(error) => println!("couldn't save {}: {}", file_path, error),
+      Ok(_) => println!("Saving profile now: {}", file_path),
+    }
+  }
+}
+
+#[test]
+fn profiler_keeps_a_rolling_history() {
+  let mut profiler = Profiler::new(3);
+  for _ in 0..5 {
+    profiler.start_frame();
+    profiler.end_phase(FramePhase::Input);
+    profiler.end_frame();
+  }
+
+  assert_eq!(profiler.history.len(), 3);
+}
+
+#[test]
+fn profiler_averages_phases_and_writes_a_row_per_frame() {
+  let mut profiler = Profiler::new(2);
+  for micros in &[100, 200, 400] {
+    profiler.current_frame = [Duration::from_micros(*micros); 6];
+    profiler.current_frame[FramePhase::Draw as usize] = Duration::from_micros(micros * 2);
+    profiler.end_frame();
+  }
+
+  assert_eq!(profiler.average(FramePhase::Input), Duration::from_micros(300));
+  assert_eq!(profiler.average(FramePhase::Draw), Duration::from_micros(600));
+  assert_eq!(Profiler::new(2).average(FramePhase::Draw), Duration::from_secs(0));
+  assert_eq!(
+    profiler.csv(),
+    "input,entity commands,level state,camera,draw,present\n\
+     200,200,200,200,400,200\n\
+     400,400,400,400,800,400\n"
+  );
+}
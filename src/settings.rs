@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Settings {
+  pub language: String,
+  // Percentages, sound effects and background music are mixed separately
+  #[serde(default = "default_volume")]
+  pub sound_volume: u8,
+  #[serde(default = "default_volume")]
+  pub music_volume: u8,
+  // Changes to the level file replace the running level, keeping the player where it is
+  #[serde(default)]
+  pub hot_reload_level: bool,
+}
+
+fn default_volume() -> u8 {
+  100
+}
+
+impl Settings {
+  // Volumes above 100% in the file are played at 100%
+  pub fn deserialize(serialized: String) -> Self {
+    let mut settings: Self = serde_json::from_str(&serialized).unwrap();
+    settings.sound_volume = settings.sound_volume.min(100);
+    settings.music_volume = settings.music_volume.min(100);
+    settings
+  }
+  pub fn save(&self, file_path: &str) {
+    write_config(file_path, self);
+  }
+}
+
+// Writes `value` with the four space indentation the files in `config` use
+pub fn write_config<T: Serialize>(file_path: &str, value: &T) {
+  let mut serialized = vec![];
+  let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+  let mut serializer = serde_json::Serializer::with_formatter(&mut serialized, formatter);
+  value.serialize(&mut serializer).unwrap();
+  serialized.push(b'\n');
+  match fs::write(file_path, serialized) {
+    Err(error) => println!("couldn't save {}: {}", file_path, error),
+    Ok(_) => println!("Saving now: {}", file_path),
+  }
+}
@@ -0,0 +1,423 @@
+use super::action_map::ActionMap;
+use super::controls::{Action, Binding, Controls, ACTIONS};
+use super::display::{Display, ScalingMode};
+use super::localization::Localization;
+use super::settings::Settings;
+use super::text::{show_text, show_text_line, Font, TextAlignment, TextLayout};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+static LETTER_SCALE: u8 = 2;
+static LETTER_GAP: f32 = 1.1;
+static ROW_HEIGHT: i32 = 22;
+static LIST_TOP: i32 = 60;
+static VOLUME_STEP: u8 = 10;
+
+static BACKGROUND_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static TITLE_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static SELECTED_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static ROW_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+static CONFLICT_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SettingsRow {
+  Action(Action),
+  MouseAim,
+  SoundVolume,
+  MusicVolume,
+  ScalingMode,
+  Fullscreen,
+}
+
+fn rows() -> Vec<SettingsRow> {
+  let mut rows = ACTIONS.iter().map(|action| SettingsRow::Action(*action)).collect::<Vec<_>>();
+  rows.extend(&[
+    SettingsRow::MouseAim,
+    SettingsRow::SoundVolume,
+    SettingsRow::MusicVolume,
+    SettingsRow::ScalingMode,
+    SettingsRow::Fullscreen,
+  ]);
+  rows
+}
+
+// What the caller has to apply or write back after the input of a tick
+#[derive(Debug, PartialEq)]
+pub enum SettingsChange {
+  None,
+  Close,
+  Controls,
+  Volume,
+  Display,
+}
+
+pub struct SettingsScreen {
+  selected: usize,
+  is_waiting_for_binding: bool,
+}
+
+impl SettingsScreen {
+  pub fn new() -> Self {
+    Self { selected: 0, is_waiting_for_binding: false }
+  }
+  pub fn is_waiting_for_binding(&self) -> bool {
+    self.is_waiting_for_binding
+  }
+  // Navigation uses the current bindings, so a rebound confirm action takes effect immediately.
+  // While waiting, `new_binding` is the first binding pressed this tick, pause or Escape cancel.
+  pub fn handle_input(
+    &mut self,
+    actions: &ActionMap,
+    new_binding: Option<Binding>,
+    controls: &mut Controls,
+    settings: &mut Settings,
+    display: &mut Display,
+  ) -> SettingsChange {
+    let row = rows()[self.selected];
+    if self.is_waiting_for_binding {
+      match (row, new_binding) {
+        (SettingsRow::Action(_), Some(binding))
+          if matches!(binding, Binding::Key { key: Keycode::Escape, .. })
+            || controls.bindings(Action::Pause).contains(&binding) =>
+        {
+          self.is_waiting_for_binding = false;
+          return SettingsChange::None;
+        }
+        (SettingsRow::Action(action), Some(binding)) => {
+          self.is_waiting_for_binding = false;
+          controls.rebind(action, binding);
+          return SettingsChange::Controls;
+        }
+        (SettingsRow::Action(_), None) => return SettingsChange::None,
+        _ => self.is_waiting_for_binding = false,
+      }
+    }
+
+    let is_decreasing = actions.is_just_pressed(Action::Left);
+    if actions.is_just_pressed(Action::Pause) {
+      SettingsChange::Close
+    } else if actions.is_just_pressed(Action::Up) {
+      self.selected = self.selected.saturating_sub(1);
+      SettingsChange::None
+    } else if actions.is_just_pressed(Action::Down) {
+      self.selected = (self.selected + 1).min(rows().len() - 1);
+      SettingsChange::None
+    } else if actions.is_just_pressed(Action::Confirm)
+      || is_decreasing
+      || actions.is_just_pressed(Action::Right)
+    {
+      match row {
+        SettingsRow::Action(_) if actions.is_just_pressed(Action::Confirm) => {
+          self.is_waiting_for_binding = true;
+          SettingsChange::None
+        }
+        SettingsRow::Action(_) => SettingsChange::None,
+        SettingsRow::MouseAim => {
+          controls.mouse_aim = !controls.mouse_aim;
+          SettingsChange::Controls
+        }
+        SettingsRow::SoundVolume => {
+          settings.sound_volume = step_volume(settings.sound_volume, is_decreasing);
+          SettingsChange::Volume
+        }
+        SettingsRow::MusicVolume => {
+          settings.music_volume = step_volume(settings.music_volume, is_decreasing);
+          SettingsChange::Volume
+        }
+        SettingsRow::ScalingMode => {
+          display.scaling_mode = match display.scaling_mode {
+            ScalingMode::Integer => ScalingMode::BestFit,
+            ScalingMode::BestFit => ScalingMode::Integer,
+          };
+          SettingsChange::Display
+        }
+        SettingsRow::Fullscreen => {
+          display.fullscreen = !display.fullscreen;
+          SettingsChange::Display
+        }
+      }
+    } else {
+      SettingsChange::None
+    }
+  }
+  fn row_text(
+    &self,
+    row: SettingsRow,
+    controls: &Controls,
+    settings: &Settings,
+    display: &Display,
+    localization: &Localization,
+  ) -> (String, String) {
+    let on_off = |is_on: bool| {
+      localization.get(if is_on { "settings.on" } else { "settings.off" }).to_string()
+    };
+    match row {
+      SettingsRow::Action(action) => (
+        localization.get(action.string_id()).to_string(),
+        controls.bindings(action).iter().map(Binding::name).collect::<Vec<_>>().join(", "),
+      ),
+      SettingsRow::MouseAim => {
+        (localization.get("settings.mouse_aim").to_string(), on_off(controls.mouse_aim))
+      }
+      SettingsRow::SoundVolume => (
+        localization.get("settings.sound_volume").to_string(),
+        format!("{}%", settings.sound_volume),
+      ),
+      SettingsRow::MusicVolume => (
+        localization.get("settings.music_volume").to_string(),
+        format!("{}%", settings.music_volume),
+      ),
+      SettingsRow::ScalingMode => (
+        localization.get("settings.scaling_mode").to_string(),
+        localization
+          .get(match display.scaling_mode {
+            ScalingMode::Integer => "settings.scaling_mode.integer",
+            ScalingMode::BestFit => "settings.scaling_mode.best_fit",
+          })
+          .to_string(),
+      ),
+      SettingsRow::Fullscreen => {
+        (localization.get("settings.fullscreen").to_string(), on_off(display.fullscreen))
+      }
+    }
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    controls: &Controls,
+    settings: &Settings,
+    display: &Display,
+  ) {
+    let dimensions = display.logical_resolution;
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.fill_rect(Rect::new(0, 0, dimensions.0 as u32, dimensions.1 as u32)).unwrap();
+    canvas.set_draw_color(original_color);
+
+    let left = dimensions.0 as i32 / 8;
+    let right = dimensions.0 as i32 - left;
+    show_text_line(
+      canvas,
+      font,
+      localization.get("settings.title"),
+      (left, LIST_TOP / 2 - 8),
+      LETTER_SCALE,
+      LETTER_GAP,
+      TITLE_COLOR,
+    );
+
+    // The list scrolls once the selection would leave the screen
+    let visible_row_count = ((dimensions.1 as i32 - LIST_TOP * 2) / ROW_HEIGHT).max(1) as usize;
+    let first_visible_row = (self.selected + 1).saturating_sub(visible_row_count);
+    let visible_rows =
+      rows().into_iter().enumerate().skip(first_visible_row).take(visible_row_count);
+    for (index, row) in visible_rows {
+      let (label, value) = self.row_text(row, controls, settings, display, localization);
+      let value = if index == self.selected && self.is_waiting_for_binding {
+        localization.get("settings.press_key").to_string()
+      } else {
+        value
+      };
+      let has_conflict = match row {
+        SettingsRow::Action(action) => !controls.conflicts(action).is_empty(),
+        _ => false,
+      };
+      let color = if has_conflict {
+        CONFLICT_COLOR
+      } else if index == self.selected {
+        SELECTED_COLOR
+      } else {
+        ROW_COLOR
+      };
+      let prefix = if index == self.selected { "> " } else { "  " };
+      let y = LIST_TOP + (index - first_visible_row) as i32 * ROW_HEIGHT;
+      show_text_line(
+        canvas,
+        font,
+        &format!("{}{}", prefix, label),
+        (left, y),
+        LETTER_SCALE,
+        LETTER_GAP,
+        color,
+      );
+      let value_layout = TextLayout::new(LETTER_SCALE)
+        .letter_gap(LETTER_GAP)
+        .box_width((right - left) as u32)
+        .alignment(TextAlignment::Right);
+      show_text(canvas, font, &value, (left, y), &value_layout, color);
+    }
+
+    let footer = match rows()[self.selected] {
+      SettingsRow::Action(action) if !controls.conflicts(action).is_empty() => {
+        let conflicts = controls
+          .conflicts(action)
+          .iter()
+          .map(|other| localization.get(other.string_id()).to_string())
+          .collect::<Vec<_>>()
+          .join(", ");
+        (localization.format("settings.conflict", &[("actions", &conflicts)]), CONFLICT_COLOR)
+      }
+      _ => (
+        localization.format(
+          "settings.hint",
+          &[
+            ("confirm", &controls.binding_name(Action::Confirm)),
+            ("back", &controls.binding_name(Action::Pause)),
+          ],
+        ),
+        ROW_COLOR,
+      ),
+    };
+    show_text_line(
+      canvas,
+      font,
+      &footer.0,
+      (left, dimensions.1 as i32 - LIST_TOP / 2 - 8),
+      LETTER_SCALE,
+      LETTER_GAP,
+      footer.1,
+    );
+  }
+}
+
+fn step_volume(volume: u8, is_decreasing: bool) -> u8 {
+  if is_decreasing {
+    volume.saturating_sub(VOLUME_STEP)
+  } else {
+    volume.saturating_add(VOLUME_STEP).min(100)
+  }
+}
+
+#[cfg(test)]
+fn press(actions: &mut ActionMap, controls: &Controls, key: Keycode) {
+  use super::action_map::InputSnapshot;
+
+  actions.update(InputSnapshot::default().input_state(controls));
+  let mut snapshot = InputSnapshot::default();
+  snapshot.keys.insert(key);
+  actions.update(snapshot.input_state(controls));
+}
+
+#[cfg(test)]
+fn test_controls() -> Controls {
+  Controls::deserialize(
+    r#"{
+      "bindings": {
+        "Jump": [{ "Key": { "key": "N" } }, { "GamepadButton": "A" }],
+        "Left": [{ "Key": { "key": "Left" } }],
+        "Right": [{ "Key": { "key": "Right" } }],
+        "Up": [{ "Key": { "key": "Up" } }],
+        "Down": [{ "Key": { "key": "Down" } }],
+        "Pause": [{ "Key": { "key": "P" } }],
+        "Confirm": [{ "Key": { "key": "Return" } }]
+      }
+    }"#
+      .to_string(),
+  )
+}
+
+#[test]
+fn settings_screen_rebinds_the_selected_action() {
+  use sdl2::controller::Button;
+
+  let mut controls = test_controls();
+  let mut settings = Settings::deserialize(r#"{ "language": "en" }"#.to_string());
+  let mut display = Display::deserialize(
+    r#"{ "logical_resolution": [300, 200], "scaling_mode": "Integer" }"#.to_string(),
+  );
+  let mut settings_screen = SettingsScreen::new();
+  let mut actions = ActionMap::new();
+
+  press(&mut actions, &controls, Keycode::Return);
+  assert_eq!(
+    settings_screen.handle_input(&actions, None, &mut controls, &mut settings, &mut display),
+    SettingsChange::None
+  );
+  assert!(settings_screen.is_waiting_for_binding());
+
+  let binding = Binding::Key { key: Keycode::J, modifiers: vec![] };
+  assert_eq!(
+    settings_screen.handle_input(
+      &actions,
+      Some(binding.clone()),
+      &mut controls,
+      &mut settings,
+      &mut display
+    ),
+    SettingsChange::Controls
+  );
+  assert_eq!(controls.bindings(Action::Jump).len(), 2);
+  assert!(controls.bindings(Action::Jump).contains(&binding));
+
+  // A gamepad binding replaces the gamepad binding and keeps the keyboard one
+  settings_screen.is_waiting_for_binding = true;
+  let gamepad_binding = Binding::GamepadButton(Button::Y);
+  settings_screen.handle_input(
+    &actions,
+    Some(gamepad_binding.clone()),
+    &mut controls,
+    &mut settings,
+    &mut display,
+  );
+  assert_eq!(controls.bindings(Action::Jump), &[binding.clone(), gamepad_binding.clone()]);
+
+  // Pause and Escape cancel the prompt without binding anything or closing the screen
+  for key in &[Keycode::P, Keycode::Escape] {
+    settings_screen.is_waiting_for_binding = true;
+    press(&mut actions, &controls, *key);
+    assert_eq!(
+      settings_screen.handle_input(
+        &actions,
+        Some(Binding::Key { key: *key, modifiers: vec![] }),
+        &mut controls,
+        &mut settings,
+        &mut display
+      ),
+      SettingsChange::None
+    );
+    assert!(!settings_screen.is_waiting_for_binding());
+    assert_eq!(controls.bindings(Action::Jump), &[binding.clone(), gamepad_binding.clone()]);
+  }
+}
+
+#[test]
+fn settings_screen_steps_volumes_and_toggles_the_display() {
+  let mut controls = test_controls();
+  let mut settings =
+    Settings::deserialize(r#"{ "language": "en", "sound_volume": 85 }"#.to_string());
+  let mut display = Display::deserialize(
+    r#"{ "logical_resolution": [300, 200], "scaling_mode": "Integer" }"#.to_string(),
+  );
+  let mut settings_screen = SettingsScreen::new();
+  let mut actions = ActionMap::new();
+  let mut handle_key = |key, settings: &mut Settings, display: &mut Display| {
+    press(&mut actions, &controls, key);
+    settings_screen.handle_input(&actions, None, &mut controls, settings, display)
+  };
+
+  for _ in 0..rows().iter().position(|row| *row == SettingsRow::SoundVolume).unwrap() {
+    handle_key(Keycode::Down, &mut settings, &mut display);
+  }
+  assert_eq!(handle_key(Keycode::Right, &mut settings, &mut display), SettingsChange::Volume);
+  assert_eq!(settings.sound_volume, 95);
+  handle_key(Keycode::Right, &mut settings, &mut display);
+  assert_eq!(settings.sound_volume, 100);
+  handle_key(Keycode::Left, &mut settings, &mut display);
+  assert_eq!(settings.sound_volume, 90);
+
+  for _ in 0..3 {
+    handle_key(Keycode::Down, &mut settings, &mut display);
+  }
+  assert_eq!(handle_key(Keycode::Return, &mut settings, &mut display), SettingsChange::Display);
+  assert!(display.fullscreen);
+  assert_eq!(handle_key(Keycode::Return, &mut settings, &mut display), SettingsChange::Display);
+  assert!(!display.fullscreen);
+
+  assert_eq!(step_volume(250, false), 100);
+  let settings = Settings::deserialize(r#"{ "language": "en", "music_volume": 250 }"#.to_string());
+  assert_eq!(settings.music_volume, 100);
+}
@@ -0,0 +1,322 @@
+use super::localization::Localization;
+use super::text::{show_text_line, Font};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Texture, WindowCanvas};
+use serde::Deserialize;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+static LETTER_SCALE: u8 = 1;
+static LETTER_GAP: f32 = 1.1;
+static ROW_HEIGHT: i32 = 12;
+static PANEL_LEFT: i32 = 30;
+static PANEL_MARGIN: i32 = 10;
+static PANEL_PADDING: i32 = 6;
+static SHEET_WIDTH: f32 = 400.0;
+static MAX_SHEET_SCALE: f32 = 4.0;
+
+static PANEL_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static PANEL_BORDER_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static TITLE_COLOR: Color = Color { r: 220, g: 140, b: 120, a: 0xff };
+static ROW_COLOR: Color = Color { r: 140, g: 140, b: 140, a: 0xff };
+static SELECTED_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static PICKED_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static REGION_COLOR: Color = Color { r: 120, g: 180, b: 255, a: 0xff };
+
+// x, y, width and height on the sprite sheet, like `Entity::sprite_sheet_rect`
+pub type SpriteRect = (i32, i32, u32, u32);
+
+// Named regions of a sprite sheet
+#[derive(Debug, Deserialize)]
+pub struct SpriteAtlas {
+  pub sprites: BTreeMap<String, SpriteRect>,
+}
+
+impl SpriteAtlas {
+  // `temples.json` next to `temples.bmp` names the regions of that sheet
+  pub fn path(sprite_sheet_name: &str) -> String {
+    format!("assets/spritesheets/{}.json", sprite_sheet_name)
+  }
+  // Sheets don't need an atlas, a missing file is none
+  pub fn load_optional(path: &str) -> Result<Option<Self>, String> {
+    if !Path::new(path).exists() {
+      return Ok(None);
+    }
+    let serialized = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    serde_json::from_str(&serialized).map(Some).map_err(|error| error.to_string())
+  }
+}
+
+// What a click on the palette did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickerClick {
+  Missed,
+  Handled,
+  AssignToSelection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PickerRow {
+  NoSprite,
+  Named(usize),
+  AssignToSelection,
+}
+
+// The palette new entities take their sprite from. Clicking the sheet picks the frame under
+// the cursor, dragging picks a region.
+pub struct SpritePicker {
+  pub is_open: bool,
+  pub picked: Option<SpriteRect>,
+  frame_size: (u32, u32),
+  sheet_size: (u32, u32),
+  atlas: Option<SpriteAtlas>,
+  // The sheet pixels where a drag started and where it is now
+  region_drag: Option<((i32, i32), (i32, i32))>,
+}
+
+impl SpritePicker {
+  pub fn new(frame_size: (u32, u32)) -> Self {
+    Self {
+      is_open: false,
+      picked: None,
+      frame_size: (frame_size.0.max(1), frame_size.1.max(1)),
+      sheet_size: (0, 0),
+      atlas: None,
+      region_drag: None,
+    }
+  }
+  pub fn set_sheet_size(&mut self, sheet_size: (u32, u32)) {
+    self.sheet_size = sheet_size;
+  }
+  pub fn set_atlas(&mut self, atlas: Option<SpriteAtlas>) {
+    self.atlas = atlas;
+  }
+  pub fn is_dragging(&self) -> bool {
+    self.region_drag.is_some()
+  }
+  fn named_sprites(&self) -> Vec<(&String, &SpriteRect)> {
+    self.atlas.iter().flat_map(|atlas| atlas.sprites.iter()).collect()
+  }
+  fn rows(&self) -> Vec<PickerRow> {
+    let mut rows = vec![PickerRow::NoSprite];
+    rows.extend((0..self.named_sprites().len()).map(PickerRow::Named));
+    rows.push(PickerRow::AssignToSelection);
+    rows
+  }
+  // Wide sheets shrink to fit the panel, small ones grow up to a limit
+  fn sheet_scale(&self) -> f32 {
+    if self.sheet_size.0 == 0 {
+      return 1.0;
+    }
+    (SHEET_WIDTH / self.sheet_size.0 as f32).min(MAX_SHEET_SCALE)
+  }
+  fn sheet_rect(&self, dimensions: (u16, u16)) -> Rect {
+    let scale = self.sheet_scale();
+    let panel_rect = self.panel_rect(dimensions);
+    Rect::new(
+      panel_rect.x() + PANEL_PADDING,
+      panel_rect.y() + PANEL_PADDING + ROW_HEIGHT,
+      ((self.sheet_size.0 as f32 * scale) as u32).max(1),
+      ((self.sheet_size.1 as f32 * scale) as u32).max(1),
+    )
+  }
+  // Sits in the bottom left corner, next to the editor buttons
+  fn panel_rect(&self, dimensions: (u16, u16)) -> Rect {
+    let scale = self.sheet_scale();
+    let width = (SHEET_WIDTH as i32).max((self.sheet_size.0 as f32 * scale) as i32);
+    let height = (self.sheet_size.1 as f32 * scale) as i32
+      + (self.rows().len() as i32 + 2) * ROW_HEIGHT
+      + PANEL_PADDING * 3;
+    Rect::new(
+      PANEL_LEFT,
+      dimensions.1 as i32 - height - PANEL_MARGIN,
+      (width + PANEL_PADDING * 2) as u32,
+      height as u32,
+    )
+  }
+  fn row_y(&self, dimensions: (u16, u16), row: usize) -> i32 {
+    self.sheet_rect(dimensions).bottom() + PANEL_PADDING + row as i32 * ROW_HEIGHT
+  }
+  // The sheet pixel under `position`, kept on the sheet
+  fn sheet_position(&self, dimensions: (u16, u16), position: (i32, i32)) -> (i32, i32) {
+    let sheet_rect = self.sheet_rect(dimensions);
+    let scale = self.sheet_scale();
+    let x = ((position.0 - sheet_rect.x()) as f32 / scale) as i32;
+    let y = ((position.1 - sheet_rect.y()) as f32 / scale) as i32;
+    (x.max(0).min(self.sheet_size.0 as i32 - 1), y.max(0).min(self.sheet_size.1 as i32 - 1))
+  }
+  pub fn mouse_down(&mut self, dimensions: (u16, u16), position: (i32, i32)) -> PickerClick {
+    if !self.is_open || !self.panel_rect(dimensions).contains_point(Point::from(position)) {
+      return PickerClick::Missed;
+    }
+    if self.sheet_rect(dimensions).contains_point(Point::from(position)) {
+      let sheet_position = self.sheet_position(dimensions, position);
+      self.region_drag = Some((sheet_position, sheet_position));
+      return PickerClick::Handled;
+    }
+    let rows = self.rows();
+    let clicked_row = (0..rows.len()).find(|row| {
+      let y = self.row_y(dimensions, *row);
+      position.1 >= y && position.1 < y + ROW_HEIGHT
+    });
+    match clicked_row.map(|row| rows[row]) {
+      Some(PickerRow::NoSprite) => self.picked = None,
+      Some(PickerRow::Named(index)) => {
+        self.picked = self.named_sprites().get(index).map(|(_, sprite_rect)| **sprite_rect);
+      }
+      Some(PickerRow::AssignToSelection) => return PickerClick::AssignToSelection,
+      None => {}
+    }
+    PickerClick::Handled
+  }
+  pub fn mouse_motion(&mut self, dimensions: (u16, u16), position: (i32, i32)) {
+    let sheet_position = self.sheet_position(dimensions, position);
+    if let Some((_, end)) = &mut self.region_drag {
+      *end = sheet_position;
+    }
+  }
+  pub fn mouse_up(&mut self) {
+    if let Some((start, end)) = self.region_drag.take() {
+      self.picked = Some(if start == end {
+        let (frame_width, frame_height) = self.frame_size;
+        (
+          start.0 / frame_width as i32 * frame_width as i32,
+          start.1 / frame_height as i32 * frame_height as i32,
+          frame_width,
+          frame_height,
+        )
+      } else {
+        region(start, end)
+      });
+    }
+  }
+  pub fn draw(
+    &self,
+    canvas: &mut WindowCanvas,
+    font: &mut Font,
+    localization: &Localization,
+    sprite_sheet: &Texture,
+    selection_count: usize,
+    dimensions: (u16, u16),
+  ) {
+    let panel_rect = self.panel_rect(dimensions);
+    let sheet_rect = self.sheet_rect(dimensions);
+    let scale = self.sheet_scale();
+    let original_color = canvas.draw_color();
+    canvas.set_draw_color(PANEL_COLOR);
+    canvas.fill_rect(panel_rect).unwrap();
+    canvas.set_draw_color(PANEL_BORDER_COLOR);
+    canvas.draw_rect(panel_rect).unwrap();
+    canvas.copy(sprite_sheet, None, Some(sheet_rect)).unwrap();
+
+    let on_sheet = |(x, y, width, height): SpriteRect| {
+      Rect::new(
+        sheet_rect.x() + (x as f32 * scale) as i32,
+        sheet_rect.y() + (y as f32 * scale) as i32,
+        ((width as f32 * scale) as u32).max(1),
+        ((height as f32 * scale) as u32).max(1),
+      )
+    };
+    canvas.set_draw_color(ROW_COLOR);
+    for (_, sprite_rect) in self.named_sprites() {
+      canvas.draw_rect(on_sheet(*sprite_rect)).unwrap();
+    }
+    if let Some(picked) = self.picked {
+      canvas.set_draw_color(PICKED_COLOR);
+      canvas.draw_rect(on_sheet(picked)).unwrap();
+    }
+    if let Some((start, end)) = self.region_drag {
+      canvas.set_draw_color(REGION_COLOR);
+      canvas.draw_rect(on_sheet(region(start, end))).unwrap();
+    }
+    canvas.set_draw_color(original_color);
+
+    let left = panel_rect.x() + PANEL_PADDING;
+    let mut show = |font: &mut Font, text: &str, y: i32, color: Color| {
+      show_text_line(canvas, font, text, (left, y), LETTER_SCALE, LETTER_GAP, color);
+    };
+    let title = match self.picked {
+      Some((x, y, width, height)) => {
+        format!("{}: {}, {}, {}, {}", localization.get("sprite_picker.title"), x, y, width, height)
+      }
+      None => localization.get("sprite_picker.title").to_string(),
+    };
+    show(font, &title, panel_rect.y() + PANEL_PADDING, TITLE_COLOR);
+    let named_sprites = self.named_sprites();
+    for (row, picker_row) in self.rows().into_iter().enumerate() {
+      let (text, is_selected) = match picker_row {
+        PickerRow::NoSprite => {
+          (localization.get("sprite_picker.none").to_string(), self.picked.is_none())
+        }
+        PickerRow::Named(index) => {
+          let (name, sprite_rect) = named_sprites[index];
+          (name.clone(), self.picked == Some(*sprite_rect))
+        }
+        PickerRow::AssignToSelection => (
+          localization.format("sprite_picker.assign", &[("count", &selection_count.to_string())]),
+          false,
+        ),
+      };
+      let color = if is_selected { SELECTED_COLOR } else { ROW_COLOR };
+      show(font, &text, self.row_y(dimensions, row), color);
+    }
+    show(
+      font,
+      localization.get("sprite_picker.hint"),
+      self.row_y(dimensions, self.rows().len()) + PANEL_PADDING,
+      ROW_COLOR,
+    );
+  }
+}
+
+// The pixels between two corners, both included
+fn region(start: (i32, i32), end: (i32, i32)) -> SpriteRect {
+  (
+    cmp::min(start.0, end.0),
+    cmp::min(start.1, end.1),
+    (start.0 - end.0).unsigned_abs() + 1,
+    (start.1 - end.1).unsigned_abs() + 1,
+  )
+}
+
+#[test]
+fn sprite_picker_picks_frames_regions_and_named_sprites() {
+  let dimensions = (900, 600);
+  let mut sprite_picker = SpritePicker::new((32, 32));
+  sprite_picker.set_sheet_size((192, 32));
+  sprite_picker.set_atlas(SpriteAtlas::load_optional(&SpriteAtlas::path("temples")).unwrap());
+  assert_eq!(sprite_picker.mouse_down(dimensions, (0, 0)), PickerClick::Missed);
+  sprite_picker.is_open = true;
+
+  // The middle of a sheet pixel on the canvas, the sheet is scaled up to fill the panel
+  let sheet_rect = sprite_picker.sheet_rect(dimensions);
+  let scale = sprite_picker.sheet_scale();
+  let on_sheet = |(x, y): (i32, i32)| {
+    (
+      sheet_rect.x() + ((x as f32 + 0.5) * scale) as i32,
+      sheet_rect.y() + ((y as f32 + 0.5) * scale) as i32,
+    )
+  };
+  sprite_picker.mouse_down(dimensions, on_sheet((40, 10)));
+  sprite_picker.mouse_up();
+  assert_eq!(sprite_picker.picked, Some((32, 0, 32, 32)));
+
+  sprite_picker.mouse_down(dimensions, on_sheet((70, 20)));
+  sprite_picker.mouse_motion(dimensions, on_sheet((64, 4)));
+  sprite_picker.mouse_up();
+  assert_eq!(sprite_picker.picked, Some((64, 4, 7, 17)));
+
+  let named_row_y = sprite_picker.row_y(dimensions, 1);
+  sprite_picker.mouse_down(dimensions, (sheet_rect.x(), named_row_y));
+  let first_named_sprite = *sprite_picker.named_sprites()[0].1;
+  assert_eq!(sprite_picker.picked, Some(first_named_sprite));
+
+  let assign_row_y = sprite_picker.row_y(dimensions, sprite_picker.rows().len() - 1);
+  assert_eq!(
+    sprite_picker.mouse_down(dimensions, (sheet_rect.x(), assign_row_y)),
+    PickerClick::AssignToSelection
+  );
+}
@@ -1,127 +1,564 @@
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, WindowCanvas};
+use sdl2::render::{Canvas, RenderTarget, Texture, TextureCreator};
+use sdl2::surface::Surface;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
-pub fn show_text_line(
-  canvas: &mut WindowCanvas,
-  texture: &mut Texture,
-  text: &str,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Glyph {
+  #[serde(default)]
+  pub page: usize,
+  pub rect: (i32, i32, u32, u32),
+  pub advance: u32,
+  // Moves the glyph away from where it sits on the baseline
+  #[serde(default = "default_glyph_offset")]
+  pub offset: (i32, i32),
+}
+
+fn default_glyph_offset() -> (i32, i32) {
+  (0, 0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlyphPage {
+  pub sprite_sheet: String,
+  pub color_key: (u8, u8, u8),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FontDescriptor {
+  pub pages: Vec<GlyphPage>,
+  pub line_height: u32,
+  pub baseline: u32,
+  pub fallback: char,
+  #[serde(default = "default_kerning")]
+  pub kerning: Vec<(char, char, i32)>,
+  pub glyphs: HashMap<char, Glyph>,
+}
+
+fn default_kerning() -> Vec<(char, char, i32)> {
+  vec![]
+}
+
+impl FontDescriptor {
+  // Drawing relies on the fallback glyph and every glyph's page existing, so a descriptor
+  // without them is rejected here instead of panicking in the middle of a frame
+  pub fn deserialize(serialized: String) -> Result<Self, String> {
+    let descriptor: Self = serde_json::from_str(&serialized).map_err(|error| error.to_string())?;
+    if !descriptor.glyphs.contains_key(&descriptor.fallback) {
+      return Err(format!("fallback {:?} has no glyph", descriptor.fallback));
+    }
+    let mut glyphs = descriptor.glyphs.iter().collect::<Vec<_>>();
+    glyphs.sort_by_key(|(character, _)| **character);
+    if let Some((character, glyph)) =
+      glyphs.iter().find(|(_, glyph)| glyph.page >= descriptor.pages.len())
+    {
+      return Err(format!("glyph {:?} is on page {} which doesn't exist", character, glyph.page));
+    }
+    Ok(descriptor)
+  }
+  // Glyphs stand on the baseline, so short ones like '.' or ',' don't float at the top of the line
+  fn glyph_top(&self, glyph: &Glyph) -> i32 {
+    self.baseline as i32 - glyph.rect.3 as i32 + glyph.offset.1
+  }
+  pub fn glyph(&self, character: char) -> &Glyph {
+    self.glyphs.get(&character).unwrap_or_else(|| &self.glyphs[&self.fallback])
+  }
+  // Characters of `text` that would be drawn with the fallback glyph, in order of appearance
+  pub fn missing_glyphs(&self, text: &str) -> Vec<char> {
+    let mut missing_glyphs = vec![];
+    for character in text.chars() {
+      if character != '\n'
+        && !self.glyphs.contains_key(&character)
+        && !missing_glyphs.contains(&character)
+      {
+        missing_glyphs.push(character);
+      }
+    }
+    missing_glyphs
+  }
+  pub fn kerning(&self, previous: char, character: char) -> i32 {
+    self
+      .kerning
+      .iter()
+      .find(|(first, second, _)| *first == previous && *second == character)
+      .map_or(0, |(_, _, amount)| *amount)
+  }
+  // Horizontal position of every character relative to the start of the line, plus the line width
+  fn layout_line(&self, text: &str, letter_scale: u8, letter_gap: f32) -> (Vec<i32>, u32) {
+    let mut positions = vec![];
+    let mut cursor = 0.0;
+    let mut previous = None;
+    for character in text.chars() {
+      if let Some(previous) = previous {
+        cursor += (self.kerning(previous, character) * letter_scale as i32) as f32;
+      }
+      positions.push(cursor as i32);
+      cursor += self.glyph(character).advance as f32 * letter_scale as f32 * letter_gap;
+      previous = Some(character);
+    }
+    (positions, cursor.max(0.0) as u32)
+  }
+}
+
+pub struct Font<'a> {
+  pub descriptor: FontDescriptor,
+  pub textures: Vec<Texture<'a>>,
+}
+
+impl<'a> Font<'a> {
+  pub fn load<T>(name: &str, texture_creator: &'a TextureCreator<T>) -> Self {
+    let descriptor = FontDescriptor::deserialize(
+      fs::read_to_string(format!("assets/fonts/{}.json", name)).unwrap(),
+    )
+    .unwrap();
+    let textures = descriptor
+      .pages
+      .iter()
+      .map(|page| {
+        let mut texture_surface =
+          Surface::load_bmp(Path::new(&format!("assets/spritesheets/{}.bmp", page.sprite_sheet)))
+            .unwrap();
+        let (r, g, b) = page.color_key;
+        texture_surface.set_color_key(true, Color { r, g, b, a: 0xff }).unwrap();
+        texture_creator.create_texture_from_surface(&texture_surface).unwrap()
+      })
+      .collect();
+
+    Self { textures, descriptor }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlignment {
+  Left,
+  Center,
+  Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+  pub box_width: Option<u32>,
+  pub alignment: TextAlignment,
+  pub letter_scale: u8,
+  pub letter_gap: f32,
+  pub line_spacing: i32,
+}
+
+impl TextLayout {
+  pub fn new(letter_scale: u8) -> Self {
+    Self {
+      box_width: None,
+      alignment: TextAlignment::Left,
+      letter_scale,
+      letter_gap: 1.1,
+      line_spacing: letter_scale as i32 * 2,
+    }
+  }
+  pub fn box_width(mut self, box_width: u32) -> Self {
+    self.box_width = Some(box_width);
+    self
+  }
+  pub fn alignment(mut self, alignment: TextAlignment) -> Self {
+    self.alignment = alignment;
+    self
+  }
+  pub fn letter_gap(mut self, letter_gap: f32) -> Self {
+    self.letter_gap = letter_gap;
+    self
+  }
+  pub fn line_spacing(mut self, line_spacing: i32) -> Self {
+    self.line_spacing = line_spacing;
+    self
+  }
+}
+
+pub struct LaidOutText {
+  pub glyphs: Vec<(char, (i32, i32), Option<Color>)>,
+  pub dimensions: (u32, u32),
+}
+
+fn parse_color(hex: &str) -> Option<Color> {
+  if hex.len() != 6 || !hex.chars().all(|character| character.is_ascii_hexdigit()) {
+    return None;
+  }
+  let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+  Some(Color { r: channel(0)?, g: channel(2)?, b: channel(4)?, a: 0xff })
+}
+
+// Splits `{#rrggbb}colored{/}` markup into characters with their color, `None` is the default
+// color; braces that are not part of the markup are kept as text
+fn parse_markup(text: &str) -> Vec<(char, Option<Color>)> {
+  let mut characters = vec![];
+  let mut color = None;
+  let mut rest = text;
+  while let Some(character) = rest.chars().next() {
+    if rest.starts_with("{/}") {
+      color = None;
+      rest = &rest[3..];
+      continue;
+    }
+    if rest.starts_with("{#") && rest.get(8..9) == Some("}") {
+      if let Some(markup_color) = rest.get(2..8).and_then(parse_color) {
+        color = Some(markup_color);
+        rest = &rest[9..];
+        continue;
+      }
+    }
+    characters.push((character, color));
+    rest = &rest[character.len_utf8()..];
+  }
+  characters
+}
+
+impl FontDescriptor {
+  fn line_width(&self, line: &[(char, Option<Color>)], letter_scale: u8, letter_gap: f32) -> u32 {
+    let text = line.iter().map(|(character, _)| character).collect::<String>();
+    self.layout_line(&text, letter_scale, letter_gap).1
+  }
+  // Greedy word wrapping, a single word wider than the box gets a line of its own
+  fn wrap_lines(&self, text: &str, layout: &TextLayout) -> Vec<Vec<(char, Option<Color>)>> {
+    let mut lines = vec![];
+    for paragraph in parse_markup(text).split(|(character, _)| *character == '\n') {
+      let mut line: Vec<(char, Option<Color>)> = vec![];
+      for word in paragraph.split(|(character, _)| *character == ' ') {
+        let mut candidate = line.clone();
+        if !candidate.is_empty() {
+          candidate.push((' ', None));
+        }
+        candidate.extend_from_slice(word);
+        let fits = match layout.box_width {
+          Some(box_width) => {
+            self.line_width(&candidate, layout.letter_scale, layout.letter_gap) <= box_width
+          }
+          None => true,
+        };
+        if fits || line.is_empty() {
+          line = candidate;
+        } else {
+          lines.push(line);
+          line = word.to_vec();
+        }
+      }
+      lines.push(line);
+    }
+    lines
+  }
+  pub fn layout_text(&self, text: &str, layout: &TextLayout) -> LaidOutText {
+    let lines = self.wrap_lines(text, layout);
+    let line_widths = lines
+      .iter()
+      .map(|line| self.line_width(line, layout.letter_scale, layout.letter_gap))
+      .collect::<Vec<_>>();
+    let width = layout.box_width.unwrap_or(*line_widths.iter().max().unwrap_or(&0));
+    let line_height = (self.line_height * layout.letter_scale as u32) as i32;
+
+    let mut glyphs = vec![];
+    for (index, (line, line_width)) in lines.iter().zip(line_widths).enumerate() {
+      let x_offset = match layout.alignment {
+        TextAlignment::Left => 0,
+        TextAlignment::Center => (width as i32 - line_width as i32) / 2,
+        TextAlignment::Right => width as i32 - line_width as i32,
+      };
+      let y_offset = index as i32 * (line_height + layout.line_spacing);
+      let text = line.iter().map(|(character, _)| character).collect::<String>();
+      let (positions, _) = self.layout_line(&text, layout.letter_scale, layout.letter_gap);
+      for ((character, color), x) in line.iter().zip(positions) {
+        glyphs.push((*character, (x_offset + x, y_offset), *color));
+      }
+    }
+
+    let height = lines.len() as i32 * line_height + (lines.len() as i32 - 1) * layout.line_spacing;
+    LaidOutText { glyphs, dimensions: (width, height.max(0) as u32) }
+  }
+}
+
+fn draw_glyph<T: RenderTarget>(
+  canvas: &mut Canvas<T>,
+  font: &mut Font,
+  character: char,
   position: (i32, i32),
   letter_scale: u8,
-  letter_gap: f32,
   color: Color,
 ) {
+  let glyph = font.descriptor.glyph(character);
+  let top = font.descriptor.glyph_top(glyph);
+  let (x, y, width, height) = glyph.rect;
+  if width == 0 || height == 0 {
+    return;
+  }
+  let texture = &mut font.textures[glyph.page];
   let color_mod = texture.color_mod();
   texture.set_color_mod(color.r, color.g, color.b);
-  let chars = text.chars().enumerate();
-  for (index, character) in chars {
-    let (x, y, width, height) = get_text_texture_rects(character);
-    let letter_width = width * letter_scale as u32;
-    let letter_height = height * letter_scale as u32;
-    canvas
-      .copy_ex(
-        &texture,
-        Some(Rect::new(x, y, width, height)),
-        Some(Rect::new(
-          position.0 + (index as f32 * letter_width as f32 * letter_gap) as i32,
-          position.1,
-          letter_width,
-          letter_height,
-        )),
-        0.0,
-        None,
-        false,
-        false,
-      )
-      .unwrap();
-  }
+  canvas
+    .copy_ex(
+      texture,
+      Some(Rect::new(x, y, width, height)),
+      Some(Rect::new(
+        position.0 + glyph.offset.0 * letter_scale as i32,
+        position.1 + top * letter_scale as i32,
+        width * letter_scale as u32,
+        height * letter_scale as u32,
+      )),
+      0.0,
+      None,
+      false,
+      false,
+    )
+    .unwrap();
   texture.set_color_mod(color_mod.0, color_mod.1, color_mod.2);
 }
 
-fn get_text_texture_rects(character: char) -> (i32, i32, u32, u32) {
-  let char_width = 7;
-  let char_height = 9;
-  match character {
-    'A' => (char_width as i32 * 0, 0, char_width, char_height),
-    'a' => (char_width as i32 * 0, char_height as i32, char_width, char_height),
-    'B' => (char_width as i32 * 1, 0, char_width, char_height),
-    'b' => (char_width as i32 * 1, char_height as i32, char_width, char_height),
-    'C' => (char_width as i32 * 2, 0, char_width, char_height),
-    'c' => (char_width as i32 * 2, char_height as i32, char_width, char_height),
-    'D' => (char_width as i32 * 3, 0, char_width, char_height),
-    'd' => (char_width as i32 * 3, char_height as i32, char_width, char_height),
-    'E' => (char_width as i32 * 4, 0, char_width, char_height),
-    'e' => (char_width as i32 * 4, char_height as i32, char_width, char_height),
-    'F' => (char_width as i32 * 5, 0, char_width, char_height),
-    'f' => (char_width as i32 * 5, char_height as i32, char_width, char_height),
-    'G' => (char_width as i32 * 6, 0, char_width, char_height),
-    'g' => (char_width as i32 * 6, char_height as i32, char_width, char_height),
-    'H' => (char_width as i32 * 7, 0, char_width, char_height),
-    'h' => (char_width as i32 * 7, char_height as i32, char_width, char_height),
-    'I' => (char_width as i32 * 8, 0, char_width, char_height),
-    'i' => (char_width as i32 * 8, char_height as i32, char_width, char_height),
-    'J' => (char_width as i32 * 9, 0, char_width, char_height),
-    'j' => (char_width as i32 * 9, char_height as i32, char_width, char_height),
-    'K' => (char_width as i32 * 10, 0, char_width, char_height),
-    'k' => (char_width as i32 * 10, char_height as i32, char_width, char_height),
-    'L' => (char_width as i32 * 11, 0, char_width, char_height),
-    'l' => (char_width as i32 * 11, char_height as i32, char_width, char_height),
-    'M' => (char_width as i32 * 12, 0, char_width, char_height),
-    'm' => (char_width as i32 * 12, char_height as i32, char_width, char_height),
-    'N' => (char_width as i32 * 13, 0, char_width, char_height),
-    'n' => (char_width as i32 * 13, char_height as i32, char_width, char_height),
-    'O' => (char_width as i32 * 14, 0, char_width, char_height),
-    'o' => (char_width as i32 * 14, char_height as i32, char_width, char_height),
-    'P' => (char_width as i32 * 15, 0, char_width, char_height),
-    'p' => (char_width as i32 * 15, char_height as i32, char_width, char_height),
-    'Q' => (char_width as i32 * 16, 0, char_width, char_height),
-    'q' => (char_width as i32 * 16, char_height as i32, char_width, char_height),
-    'R' => (char_width as i32 * 17, 0, char_width, char_height),
-    'r' => (char_width as i32 * 17, char_height as i32, char_width, char_height),
-    'S' => (char_width as i32 * 18, 0, char_width, char_height),
-    's' => (char_width as i32 * 18, char_height as i32, char_width, char_height),
-    'T' => (char_width as i32 * 19, 0, char_width, char_height),
-    't' => (char_width as i32 * 19, char_height as i32, char_width, char_height),
-    'U' => (char_width as i32 * 20, 0, char_width, char_height),
-    'u' => (char_width as i32 * 20, char_height as i32, char_width, char_height),
-    'V' => (char_width as i32 * 21, 0, char_width, char_height),
-    'v' => (char_width as i32 * 21, char_height as i32, char_width, char_height),
-    'W' => (char_width as i32 * 22, 0, char_width, char_height),
-    'w' => (char_width as i32 * 22, char_height as i32, char_width, char_height),
-    'X' => (char_width as i32 * 23, 0, char_width, char_height),
-    'x' => (char_width as i32 * 23, char_height as i32, char_width, char_height),
-    'Y' => (char_width as i32 * 24, 0, char_width, char_height),
-    'y' => (char_width as i32 * 24, char_height as i32, char_width, char_height),
-    'Z' => (char_width as i32 * 25, 0, char_width, char_height),
-    'z' => (char_width as i32 * 25, char_height as i32, char_width, char_height),
-    '0' => (char_width as i32 * 26, 0, char_width, char_height),
-    '+' => (char_width as i32 * 26, char_height as i32, char_width, char_height),
-    '1' => (char_width as i32 * 27, 0, char_width, char_height),
-    '-' => (char_width as i32 * 27, char_height as i32, char_width, char_height),
-    '2' => (char_width as i32 * 28, 0, char_width, char_height),
-    '/' => (char_width as i32 * 28, char_height as i32, char_width, char_height),
-    '3' => (char_width as i32 * 29, 0, char_width, char_height),
-    '\\' => (char_width as i32 * 29, char_height as i32, char_width, char_height),
-    '4' => (char_width as i32 * 30, 0, char_width, char_height),
-    '5' => (char_width as i32 * 31, 0, char_width, char_height),
-    '6' => (char_width as i32 * 32, 0, char_width, char_height),
-    '7' => (char_width as i32 * 33, 0, char_width, char_height),
-    '8' => (char_width as i32 * 34, 0, char_width, char_height),
-    '9' => (char_width as i32 * 35, 0, char_width, char_height),
-    ',' => (char_width as i32 * 36, 0, char_width, char_height),
-    '.' => (char_width as i32 * 37, 0, char_width, char_height),
-    '!' => (char_width as i32 * 38, 0, char_width, char_height),
-    '?' => (char_width as i32 * 39, 0, char_width, char_height),
-    ':' => (char_width as i32 * 40, 0, char_width, char_height),
-    ';' => (char_width as i32 * 41, 0, char_width, char_height),
-    '"' => (char_width as i32 * 42, 0, char_width, char_height),
-    '{' => (char_width as i32 * 43, 0, char_width, char_height),
-    '}' => (char_width as i32 * 44, 0, char_width, char_height),
-    '[' => (char_width as i32 * 45, 0, char_width, char_height),
-    ']' => (char_width as i32 * 46, 0, char_width, char_height),
-    '(' => (char_width as i32 * 47, 0, char_width, char_height),
-    ')' => (char_width as i32 * 48, 0, char_width, char_height),
-    ' ' => (0, 0, 0, 0),
-    _ => (5000, 0, char_width, char_height),
+fn collect_strings<'a>(value: &'a serde_json::Value, strings: &mut Vec<&'a str>) {
+  match value {
+    serde_json::Value::String(string) => strings.push(string),
+    serde_json::Value::Array(values) => {
+      values.iter().for_each(|value| collect_strings(value, strings));
+    }
+    serde_json::Value::Object(map) => {
+      map.values().for_each(|value| collect_strings(value, strings))
+    }
+    _ => {}
   }
 }
+
+// File, text and the characters of the text without a glyph
+type MissingGlyphs = (String, String, Vec<char>);
+
+// Goes through every string in the JSON files of `directories` and returns the file, the text and
+// the characters `descriptor` has no glyph for, plus the files that couldn't be read or parsed
+pub fn find_missing_glyphs(
+  descriptor: &FontDescriptor,
+  directories: &[&str],
+) -> (Vec<MissingGlyphs>, Vec<(String, String)>) {
+  let mut missing_glyphs = vec![];
+  let mut unreadable_files = vec![];
+  for directory in directories {
+    let mut paths = match fs::read_dir(directory) {
+      Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+      Err(_) => vec![],
+    };
+    paths.sort();
+    for path in
+      paths.iter().filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+    {
+      let value = fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|file| {
+        serde_json::from_str::<serde_json::Value>(&file).map_err(|error| error.to_string())
+      });
+      let value = match value {
+        Ok(value) => value,
+        Err(error) => {
+          unreadable_files.push((path.display().to_string(), error));
+          continue;
+        }
+      };
+      let mut strings = vec![];
+      collect_strings(&value, &mut strings);
+      for string in strings {
+        let missing = descriptor.missing_glyphs(string);
+        if !missing.is_empty() {
+          missing_glyphs.push((path.display().to_string(), string.to_string(), missing));
+        }
+      }
+    }
+  }
+  (missing_glyphs, unreadable_files)
+}
+
+pub fn measure_text(font: &Font, text: &str, layout: &TextLayout) -> (u32, u32) {
+  font.descriptor.layout_text(text, layout).dimensions
+}
+
+// Number of glyphs laid out for `text`, neither markup nor the spaces dropped at line breaks count
+pub fn visible_length(descriptor: &FontDescriptor, text: &str, layout: &TextLayout) -> usize {
+  descriptor.layout_text(text, layout).glyphs.len()
+}
+
+// Draws wrapped and aligned text and returns the area it takes up on the canvas
+pub fn show_text<T: RenderTarget>(
+  canvas: &mut Canvas<T>,
+  font: &mut Font,
+  text: &str,
+  position: (i32, i32),
+  layout: &TextLayout,
+  color: Color,
+) -> Rect {
+  show_text_revealed(canvas, font, text, position, layout, color, usize::MAX)
+}
+
+// Like `show_text`, but only draws the first `revealed_characters`. The whole text is laid out
+// first, so words don't jump to the next line while they are being revealed
+pub fn show_text_revealed<T: RenderTarget>(
+  canvas: &mut Canvas<T>,
+  font: &mut Font,
+  text: &str,
+  position: (i32, i32),
+  layout: &TextLayout,
+  color: Color,
+  revealed_characters: usize,
+) -> Rect {
+  let laid_out_text = font.descriptor.layout_text(text, layout);
+  for (character, (x, y), glyph_color) in laid_out_text.glyphs.into_iter().take(revealed_characters)
+  {
+    draw_glyph(
+      canvas,
+      font,
+      character,
+      (position.0 + x, position.1 + y),
+      layout.letter_scale,
+      glyph_color.unwrap_or(color),
+    );
+  }
+
+  Rect::new(
+    position.0,
+    position.1,
+    laid_out_text.dimensions.0.max(1),
+    laid_out_text.dimensions.1.max(1),
+  )
+}
+
+pub fn measure_text_line(font: &Font, text: &str, letter_scale: u8, letter_gap: f32) -> (u32, u32) {
+  let (_, width) = font.descriptor.layout_line(text, letter_scale, letter_gap);
+  (width, font.descriptor.line_height * letter_scale as u32)
+}
+
+pub fn show_text_line<T: RenderTarget>(
+  canvas: &mut Canvas<T>,
+  font: &mut Font,
+  text: &str,
+  position: (i32, i32),
+  letter_scale: u8,
+  letter_gap: f32,
+  color: Color,
+) {
+  let (positions, _) = font.descriptor.layout_line(text, letter_scale, letter_gap);
+  for (character, x_offset) in text.chars().zip(positions) {
+    draw_glyph(canvas, font, character, (position.0 + x_offset, position.1), letter_scale, color);
+  }
+}
+
+#[test]
+fn font_descriptor_measures_variable_width_glyphs_with_kerning() {
+  let descriptor = FontDescriptor::deserialize(
+    r#"{
+      "pages": [{ "sprite_sheet": "text", "color_key": [128, 128, 128] }],
+      "line_height": 9,
+      "baseline": 9,
+      "fallback": "?",
+      "kerning": [["A", "V", -1]],
+      "glyphs": {
+        "?": { "rect": [0, 0, 5, 9], "advance": 6 },
+        "A": { "rect": [0, 0, 7, 9], "advance": 8 },
+        "V": { "rect": [7, 0, 7, 9], "advance": 8 },
+        "i": { "rect": [14, 0, 3, 9], "advance": 4 }
+      }
+    }"#
+      .to_string(),
+  )
+  .unwrap();
+
+  let (positions, width) = descriptor.layout_line("AVi~", 2, 1.0);
+
+  assert_eq!(positions, vec![0, 14, 30, 38]);
+  assert_eq!(width, 50);
+  assert_eq!(descriptor.glyph('~').rect, (0, 0, 5, 9));
+}
+
+#[test]
+fn font_descriptor_rejects_missing_fallback_glyphs_and_pages() {
+  let descriptor = |fallback: &str, page: usize| {
+    FontDescriptor::deserialize(format!(
+      r#"{{
+        "pages": [{{ "sprite_sheet": "text", "color_key": [128, 128, 128] }}],
+        "line_height": 9,
+        "baseline": 9,
+        "fallback": "{}",
+        "glyphs": {{ "?": {{ "page": {}, "rect": [0, 0, 5, 6], "advance": 6 }} }}
+      }}"#,
+      fallback, page
+    ))
+  };
+
+  let valid_descriptor = descriptor("?", 0).unwrap();
+  assert_eq!(valid_descriptor.glyph_top(valid_descriptor.glyph('?')), 3);
+  assert_eq!(descriptor("~", 0).err().unwrap(), "fallback '~' has no glyph");
+  assert_eq!(descriptor("?", 1).err().unwrap(), "glyph '?' is on page 1 which doesn't exist");
+}
+
+#[test]
+fn text_layout_wraps_aligns_and_colors() {
+  let mut glyphs = HashMap::new();
+  for character in "?abcdefghijklmnopqrstuvwxyz {}#/0123456789".chars() {
+    glyphs.insert(character, Glyph { page: 0, rect: (0, 0, 7, 9), advance: 7, offset: (0, 0) });
+  }
+  let descriptor = FontDescriptor {
+    pages: vec![GlyphPage { sprite_sheet: "text".to_string(), color_key: (128, 128, 128) }],
+    line_height: 9,
+    baseline: 9,
+    fallback: '?',
+    kerning: vec![],
+    glyphs,
+  };
+  let layout = TextLayout::new(1)
+    .box_width(35)
+    .alignment(TextAlignment::Right)
+    .letter_gap(1.0)
+    .line_spacing(3);
+
+  let laid_out_text = descriptor.layout_text("ab {#ff0000}cde{/} f\nghi", &layout);
+
+  assert_eq!(laid_out_text.dimensions, (35, 9 * 3 + 3 * 2));
+  assert_eq!(laid_out_text.glyphs[0], ('a', (21, 0), None));
+  assert_eq!(laid_out_text.glyphs[2], ('c', (0, 12), Some(Color { r: 255, g: 0, b: 0, a: 0xff })));
+  assert_eq!(laid_out_text.glyphs[6], ('f', (28, 12), None));
+  assert_eq!(laid_out_text.glyphs[7], ('g', (14, 24), None));
+  assert_eq!(visible_length(&descriptor, "ab {#ff0000}cde{/} f\nghi", &layout), 10);
+  // Multi-byte characters where the markup would be are kept as text
+  assert_eq!(parse_markup("{#aäää").len(), 6);
+  assert_eq!(parse_markup("{#1ä234}").len(), 8);
+}
+
+#[test]
+fn missing_glyphs_are_reported_once() {
+  let descriptor =
+    FontDescriptor::deserialize(fs::read_to_string("assets/fonts/default.json").unwrap()).unwrap();
+
+  assert_eq!(descriptor.missing_glyphs("Grüße, 100% \u{263A}\u{263A}!"), vec!['\u{263A}']);
+  assert_eq!(descriptor.glyph('\u{263A}').rect, descriptor.glyph('\u{FFFD}').rect);
+}
+
+#[test]
+fn default_font_has_narrow_glyphs_and_kerning() {
+  let descriptor =
+    FontDescriptor::deserialize(fs::read_to_string("assets/fonts/default.json").unwrap()).unwrap();
+
+  for character in "il.,!':".chars() {
+    assert!(descriptor.glyph(character).advance < descriptor.glyph('m').advance);
+  }
+  assert_eq!(descriptor.kerning('A', 'V'), -1);
+  assert_eq!(descriptor.layout_line("Hi!", 1, 1.0), (vec![0, 7, 10], 13));
+  assert_eq!(descriptor.layout_line("AV", 1, 1.0), (vec![0, 6], 13));
+}
+
+#[test]
+fn missing_glyph_check_reports_unreadable_files_and_goes_on() {
+  let descriptor =
+    FontDescriptor::deserialize(fs::read_to_string("assets/fonts/default.json").unwrap()).unwrap();
+  let directory = std::env::temp_dir().join(format!("glyph_check_{}", std::process::id()));
+  fs::create_dir_all(&directory).unwrap();
+  fs::write(directory.join("a_broken.json"), "{ \"text\": ").unwrap();
+  fs::write(directory.join("b_sign.json"), "{ \"text\": \"\u{263A}\" }").unwrap();
+
+  let (missing_glyphs, unreadable_files) =
+    find_missing_glyphs(&descriptor, &[directory.to_str().unwrap()]);
+  fs::remove_dir_all(&directory).unwrap();
+
+  assert_eq!(missing_glyphs.len(), 1);
+  assert_eq!(missing_glyphs[0].2, vec!['\u{263A}']);
+  assert_eq!(unreadable_files.len(), 1);
+  assert!(unreadable_files[0].0.ends_with("a_broken.json"));
+}
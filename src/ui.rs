@@ -1,28 +1,85 @@
 use super::editor_menu::{EditorMenu, LevelEntityVariant};
+use super::text::{measure_text, show_text, Font, TextAlignment, TextLayout};
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 use sdl2::render::{Texture, WindowCanvas};
 use std::mem;
 
-pub fn draw_pause_menu(canvas: &mut WindowCanvas) {
+pub static PANEL_PADDING: i32 = 8;
+static PANEL_COLOR: Color = Color { r: 30, g: 30, b: 26, a: 0xff };
+static PANEL_BORDER_COLOR: Color = Color { r: 255, g: 60, b: 60, a: 0xff };
+static RETICLE_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+static RETICLE_GAP: i32 = 3;
+static RETICLE_SIZE: i32 = 9;
+
+// Draws text on a bordered panel, `position` is the top left corner of the panel
+pub fn draw_text_panel(
+  canvas: &mut WindowCanvas,
+  font: &mut Font,
+  text: &str,
+  position: (i32, i32),
+  layout: &TextLayout,
+  color: Color,
+) -> Rect {
+  let (width, height) = measure_text(font, text, layout);
+  let panel_rect = Rect::new(
+    position.0,
+    position.1,
+    width + PANEL_PADDING as u32 * 2,
+    height + PANEL_PADDING as u32 * 2,
+  );
   let original_color = canvas.draw_color();
-  canvas.set_draw_color(Color { r: 255, g: 60, b: 60, a: 0xff });
+  canvas.set_draw_color(PANEL_COLOR);
+  canvas.fill_rect(panel_rect).unwrap();
+  canvas.set_draw_color(PANEL_BORDER_COLOR);
+  canvas.draw_rect(panel_rect).unwrap();
+  canvas.set_draw_color(original_color);
 
-  let stop_width = 8u32;
-  let stop_height = 20u32;
-  let bar_gap = 4i32;
-  let stop_1_bar_x = 30i32;
-  let stop_1_bar_y = 0i32;
+  show_text(
+    canvas,
+    font,
+    text,
+    (position.0 + PANEL_PADDING, position.1 + PANEL_PADDING),
+    layout,
+    color,
+  );
+  panel_rect
+}
 
-  canvas.fill_rect(Rect::new(stop_1_bar_x, stop_1_bar_y, stop_width, stop_height)).unwrap();
-  canvas
-    .fill_rect(Rect::new(
-      stop_1_bar_x + stop_width as i32 + bar_gap,
-      stop_1_bar_y,
-      stop_width,
-      stop_height,
-    ))
-    .unwrap();
+// Draws centered lines on a panel in the middle of the screen
+pub fn draw_message_panel(
+  canvas: &mut WindowCanvas,
+  font: &mut Font,
+  text: &str,
+  dimensions: (u16, u16),
+) -> Rect {
+  let layout = TextLayout::new(2).alignment(TextAlignment::Center);
+  let (width, height) = measure_text(font, text, &layout);
+  draw_text_panel(
+    canvas,
+    font,
+    text,
+    (
+      (dimensions.0 as i32 - width as i32) / 2 - PANEL_PADDING,
+      (dimensions.1 as i32 - height as i32) / 2 - PANEL_PADDING,
+    ),
+    &layout,
+    Color { r: 255, g: 255, b: 255, a: 0xff },
+  )
+}
+
+// A crosshair with a gap in the middle, so whatever is aimed at stays visible
+pub fn draw_reticle(canvas: &mut WindowCanvas, (x, y): (i32, i32)) {
+  let original_color = canvas.draw_color();
+  canvas.set_draw_color(RETICLE_COLOR);
+  for (direction_x, direction_y) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+    canvas
+      .draw_line(
+        Point::new(x + direction_x * RETICLE_GAP, y + direction_y * RETICLE_GAP),
+        Point::new(x + direction_x * RETICLE_SIZE, y + direction_y * RETICLE_SIZE),
+      )
+      .unwrap();
+  }
   canvas.set_draw_color(original_color);
 }
 
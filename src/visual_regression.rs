@@ -0,0 +1,190 @@
+// Renders scenes through SDL's software renderer and compares them with the golden images in
+// `tests/golden`. A missing golden image fails the test, set UPDATE_GOLDEN=1 to record new ones
+// or re-record all of them after an intended visual change. Failures write the actual image and a
+// diff image into `target/visual-diff`.
+use super::camera::Camera;
+use super::capture::read_surface;
+use super::entity::Entity;
+use super::level::Level;
+use super::text::{measure_text_line, show_text_line, Font};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::{Canvas, Texture};
+use sdl2::surface::Surface;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+static BACKGROUND_COLOR: Color = Color { r: 42, g: 43, b: 37, a: 0xff };
+static DIMENSIONS: (u16, u16) = (320, 200);
+
+// Largest difference per color channel that still counts as the same pixel
+static CHANNEL_TOLERANCE: u8 = 2;
+// Share of pixels that may differ before the comparison fails
+static PIXEL_TOLERANCE: f32 = 0.001;
+
+fn empty_level() -> Level {
+  Level {
+    name: None,
+    background: vec![],
+    indestructible: vec![],
+    destructible: vec![],
+    enemies: vec![],
+    main_character: vec![],
+    effects: vec![],
+    foreground: vec![],
+  }
+}
+
+fn render<F>(draw: F) -> Surface<'static>
+where
+  F: FnOnce(&mut Canvas<Surface<'static>>, &Texture, &mut Font),
+{
+  let surface =
+    Surface::new(DIMENSIONS.0 as u32, DIMENSIONS.1 as u32, PixelFormatEnum::ARGB8888).unwrap();
+  let mut canvas = surface.into_canvas().unwrap();
+  let texture_creator = canvas.texture_creator();
+  let entity_texture = {
+    let mut texture_surface =
+      Surface::load_bmp(Path::new("assets/spritesheets/temples.bmp")).unwrap();
+    texture_surface.set_color_key(true, Color { r: 0, g: 0, b: 0, a: 0xff }).unwrap();
+    texture_creator.create_texture_from_surface(&texture_surface).unwrap()
+  };
+  let mut font = Font::load("default", &texture_creator);
+
+  canvas.set_draw_color(BACKGROUND_COLOR);
+  canvas.clear();
+  draw(&mut canvas, &entity_texture, &mut font);
+
+  read_surface(&canvas, (DIMENSIONS.0 as u32, DIMENSIONS.1 as u32))
+}
+
+fn render_level(level: &Level, camera: &mut Camera) -> Surface<'static> {
+  render(|canvas, entity_texture, _| level.draw(camera, canvas, entity_texture))
+}
+
+// Returns the number of differing pixels and an image marking them in red
+fn compare(actual: &Surface, expected: &Surface) -> (usize, Surface<'static>) {
+  let mut diff = Surface::new(actual.width(), actual.height(), PixelFormatEnum::ARGB8888).unwrap();
+  let pitch = actual.pitch() as usize;
+  let mut differing_pixels = 0;
+
+  let actual_pixels = actual.without_lock().unwrap();
+  let expected_pixels = expected.without_lock().unwrap();
+  let diff_pixels = diff.without_lock_mut().unwrap();
+  for y in 0..actual.height() as usize {
+    for x in 0..actual.width() as usize {
+      let index = y * pitch + x * 4;
+      let is_same = (0..4).all(|channel| {
+        let (a, b) = (actual_pixels[index + channel], expected_pixels[index + channel]);
+        (a as i16 - b as i16).abs() <= CHANNEL_TOLERANCE as i16
+      });
+      // ARGB8888 is stored as B, G, R, A on little endian machines
+      let pixel = if is_same {
+        let gray = actual_pixels[index + 1] / 3;
+        [gray, gray, gray, 0xff]
+      } else {
+        differing_pixels += 1;
+        [0, 0, 0xff, 0xff]
+      };
+      diff_pixels[index..index + 4].copy_from_slice(&pixel);
+    }
+  }
+  (differing_pixels, diff)
+}
+
+fn assert_matches_golden(actual: Surface, name: &str) {
+  let golden_path = format!("tests/golden/{}.bmp", name);
+  if env::var("UPDATE_GOLDEN").is_ok() {
+    fs::create_dir_all("tests/golden").unwrap();
+    actual.save_bmp(Path::new(&golden_path)).unwrap();
+    println!("Recorded golden image {}", golden_path);
+    return;
+  }
+  assert!(
+    Path::new(&golden_path).exists(),
+    "{} has no golden image, run the tests with UPDATE_GOLDEN=1 to record {}",
+    name,
+    golden_path
+  );
+
+  let expected = Surface::load_bmp(Path::new(&golden_path))
+    .unwrap()
+    .convert_format(PixelFormatEnum::ARGB8888)
+    .unwrap();
+  assert_eq!(
+    (actual.width(), actual.height()),
+    (expected.width(), expected.height()),
+    "{} has different dimensions than its golden image",
+    name
+  );
+
+  let (differing_pixels, diff) = compare(&actual, &expected);
+  let allowed_pixels = (actual.width() * actual.height()) as f32 * PIXEL_TOLERANCE;
+  if differing_pixels as f32 > allowed_pixels {
+    fs::create_dir_all("target/visual-diff").unwrap();
+    actual.save_bmp(Path::new(&format!("target/visual-diff/{}.actual.bmp", name))).unwrap();
+    diff.save_bmp(Path::new(&format!("target/visual-diff/{}.diff.bmp", name))).unwrap();
+    panic!(
+      "{} differs from {} in {} pixels, see target/visual-diff/{}.diff.bmp",
+      name, golden_path, differing_pixels, name
+    );
+  }
+}
+
+#[test]
+fn draw_relatively_culls_entities_outside_of_the_camera() {
+  let mut level = empty_level();
+  level.indestructible = vec![
+    Entity::new(-20.0, -20.0, 40, 40),
+    Entity::new(150.0, 90.0, 40, 40),
+    Entity::new(5000.0, 0.0, 40, 40),
+    Entity::new(-5000.0, -5000.0, 40, 40),
+  ];
+
+  assert_matches_golden(render_level(&level, &mut Camera::new(DIMENSIONS)), "culling");
+}
+
+#[test]
+fn draw_relatively_moves_parallax_layers_slower() {
+  let mut level = empty_level();
+  level.background = vec![Entity::new(0.0, 0.0, 30, 60).parallax_x(0.5).parallax_y(0.5)];
+  level.indestructible = vec![Entity::new(0.0, 60.0, 200, 10)];
+  let mut camera = Camera::new(DIMENSIONS);
+  camera.position = (80.0, 20.0);
+
+  assert_matches_golden(render_level(&level, &mut camera), "parallax");
+}
+
+#[test]
+fn draw_relatively_scales_with_camera_zoom() {
+  let mut level = empty_level();
+  level.indestructible = vec![Entity::new(0.0, 0.0, 20, 20), Entity::new(40.0, 10.0, 10, 30)];
+  let mut sprite = Entity::new(-40.0, -40.0, 32, 32);
+  sprite.sprite_sheet_rect = Some((0, 0, 32, 32));
+  level.main_character = vec![sprite];
+  let mut camera = Camera::new(DIMENSIONS);
+  camera.zoom(2.0);
+
+  assert_matches_golden(render_level(&level, &mut camera), "zoom");
+}
+
+#[test]
+fn show_text_line_renders_letters_digits_and_punctuation() {
+  let rendered = render(|canvas, _, font| {
+    show_text_line(
+      canvas,
+      font,
+      "Hello World 0123456789",
+      (10, 10),
+      1,
+      1.1,
+      Color { r: 220, g: 140, b: 120, a: 0xff },
+    );
+    // Kerned pairs and the punctuation at the end only count if they are on the canvas
+    let text = "Level 1-2: LAVA!?";
+    assert!(10 + measure_text_line(font, text, 2, 1.1).0 <= DIMENSIONS.0 as u32);
+    show_text_line(canvas, font, text, (10, 40), 2, 1.1, Color { r: 255, g: 255, b: 255, a: 0xff });
+  });
+
+  assert_matches_golden(rendered, "text");
+}